@@ -125,6 +125,39 @@ fn target_12(c: &mut Criterion) {
     });
 }
 
+fn target_12_step(c: &mut Criterion) {
+    const NSTEPS: usize = 1_000_000;
+
+    let day12 = fs::read_to_string("data/12.txt").unwrap();
+    let reader = io::BufReader::new(day12.as_bytes());
+    let system = aoc2019::day12::parse_input(reader).unwrap();
+    let moons = aoc2019::day12::Moons::from_system(&system).unwrap();
+
+    c.bench_function("day_12_step_scalar", |b| {
+        b.iter(|| {
+            let mut moons = moons.clone();
+            for _ in 0..NSTEPS {
+                moons.step_scalar();
+            }
+            moons.energy()
+        })
+    });
+
+    if moons.clone().step_simd().is_err() {
+        eprintln!("Skipping day_12_step_simd since AVX2 is not available on this CPU.");
+        return;
+    }
+    c.bench_function("day_12_step_simd", |b| {
+        b.iter(|| {
+            let mut moons = moons.clone();
+            for _ in 0..NSTEPS {
+                moons.step_simd().unwrap();
+            }
+            moons.energy()
+        })
+    });
+}
+
 fn target_13(c: &mut Criterion) {
     let day13 = fs::read_to_string("data/13.txt").unwrap();
     c.bench_function("day_13", |b| {
@@ -167,7 +200,7 @@ criterion_group! {
 criterion_group! {
     name = day_12;
     config = Criterion::default().warm_up_time(Duration::from_secs(5)).sample_size(10);
-    targets = target_12
+    targets = target_12, target_12_step
 }
 
 criterion_main!(group, day_12);
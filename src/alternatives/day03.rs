@@ -12,14 +12,26 @@ pub fn run<R>(input: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
-    let mut state: State = HashMap::with_capacity(1024 * 1024);
+    run_with_capacity(input, 1024 * 1024)
+}
 
-    let mut id = 0;
+/// Like `run`, but with room for `capacity` points in the map up front.
+pub(crate) fn run_with_capacity<R>(input: R, capacity: usize) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    let mut state: State = HashMap::with_capacity(capacity);
 
+    let mut id = 0;
     for res in input.lines() {
-        assert!(id < 2);
-
         let line = res?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if id > 1 {
+            bail!("Invalid input. Input must be comprise of exactly 2 paths.")
+        }
 
         let mut point = ORIGIN;
         let mut steps = 0;
@@ -36,9 +48,9 @@ where
 
     let (answer1, answer2) = state
         .iter()
-        .filter(|(_, v)| v[0].is_some() && v[1].is_some())
+        .filter(|(point, v)| **point != ORIGIN && v[0].is_some() && v[1].is_some())
         .fold(
-            (std::u32::MAX, std::u32::MAX),
+            (u32::MAX, u32::MAX),
             |(mut min_dist, mut min_steps), (point, array)| {
                 let dist = manhattan_distance(*point, ORIGIN);
                 if dist < min_dist {
@@ -54,6 +66,10 @@ where
             },
         );
 
+    if answer1 == u32::MAX {
+        bail!("Unable to find any intersections.")
+    }
+
     Ok((answer1.to_string(), answer2.to_string()))
 }
 
@@ -99,7 +115,10 @@ impl std::str::FromStr for Instruction {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let bytes = s.as_bytes();
-        let dir = Direction::try_from(bytes[0] as char)?;
+        let dir = match bytes.first() {
+            Some(&b) => Direction::try_from(b as char)?,
+            None => bail!("Unable to parse an empty string into an instruction."),
+        };
         let dist = atoi::atoi::<u32>(&bytes[1..])
             .ok_or_else(|| error!("Unable to parse {} into an instruction", s))?;
         Ok(Instruction { dir, dist })
@@ -160,4 +179,14 @@ mod tests {
             assert_eq!(&actual2, expected2);
         }
     }
+
+    #[test]
+    fn test_03_invalid() {
+        let e = run("R8,U5\nU7,R6\nL4".as_bytes()).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Invalid input. Input must be comprise of exactly 2 paths."
+        );
+        assert!(run("R8,,U5\nU7,R6".as_bytes()).is_err());
+    }
 }
@@ -1,6 +1,12 @@
-use std::collections::VecDeque;
-use std::convert::TryFrom;
+use std::collections::{HashMap, VecDeque};
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+use std::hash::Hash;
 use std::io;
+use std::num::ParseIntError;
+use std::ops::{Add, Deref, Mul};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use crossbeam::channel::{self, Receiver, Sender};
@@ -10,77 +16,334 @@ use crate::error::Error;
 pub type ComputerST = Computer<VecDeque<i64>>;
 pub type ComputerMT = Computer<Channel<i64>>;
 
+/// Callback given the program counter and disassembly of each executed instruction.
+pub type TraceFn = Box<dyn FnMut(u64, &str) + Send>;
+
+/// Callback that supplies the value for each input instruction. See
+/// `Computer::set_input_fn`.
+pub type InputFn<N = i64> = Box<dyn FnMut() -> Result<N, Error> + Send>;
+
+/// Callback that receives each output in place of the output queue. See
+/// `Computer::set_output_sink`.
+pub type OutputFn<N = i64> = Box<dyn FnMut(N) + Send>;
+
+/// How many times each opcode has been executed. See `Computer::enable_profiling`.
+pub type Profile = HashMap<u64, u64>;
+
+/// A type that can be used for the memory cells of a `Computer`. Intcode is
+/// specified in terms of `i64`, which is what every day uses, but `i128` is
+/// available for programs whose arithmetic would overflow it. Pointers, opcodes,
+/// and relative base adjustments must still fit in an `i64`.
+pub trait Cell:
+    Copy
+    + Default
+    + Ord
+    + Hash
+    + fmt::Debug
+    + fmt::Display
+    + Add<Output = Self>
+    + Mul<Output = Self>
+    + From<i64>
+    + TryInto<i64>
+    + FromStr<Err = ParseIntError>
+    + Send
+    + 'static
+{
+}
+
+impl Cell for i64 {}
+impl Cell for i128 {}
+
+fn to_i64<N>(n: N) -> Result<i64, Error>
+where
+    N: Cell,
+{
+    n.try_into()
+        .map_err(|_| error!("Value {} does not fit in an i64.", n))
+}
+
+/// An intcode computer whose input and output queues are of type `Q`, e.g.
+/// `VecDeque` for driving it by hand on one thread (`ComputerST`) or `Channel` for
+/// running it on a thread of its own (`ComputerMT`).
+///
+/// ```
+/// use aoc2019::{ComputerST, Queue, Rom};
+///
+/// // Outputs its input plus one.
+/// let rom = Rom::from_reader("3,0,1001,0,1,0,4,0,99".as_bytes())?;
+/// let mut computer = ComputerST::new(&rom);
+/// computer.input_mut().enqueue(41)?;
+/// computer.run()?;
+/// assert_eq!(computer.output_mut().dequeue()?, 42);
+/// # Ok::<(), aoc2019::Error>(())
+/// ```
 #[derive(Clone, Debug)]
-pub struct Computer<Q> {
+pub struct Computer<Q, N = i64> {
     pc: u64, // Program counter
     rb: i64, // Relative base
-    ram: Vec<i64>,
+    ram: Ram<N>,
+    program_len: usize,
+    state: StateInternal,
+    last_state: Option<State>,
+    input: Q,
+    output: Q,
+    hook: Option<TraceHook>,
+    input_fn: Option<InputHook<N>>,
+    output_fn: Option<OutputHook<N>>,
+    label: Option<String>,
+    profile: Option<Profile>,
+    dump_on_error: bool,
+    input_timeout: Option<Duration>,
+    checkpoints: HashMap<String, ComputerSnapshot<Q, N>>,
+}
+
+/// Everything needed to put a `Computer` back exactly as it was; see
+/// `Computer::checkpoint`.
+#[derive(Clone, Debug)]
+struct ComputerSnapshot<Q, N> {
+    pc: u64,
+    rb: i64,
+    ram: Ram<N>,
     state: StateInternal,
+    last_state: Option<State>,
     input: Q,
     output: Q,
 }
 
-impl Computer<VecDeque<i64>> {
-    pub fn new<R>(rom: R) -> Computer<VecDeque<i64>>
+impl<N> Computer<VecDeque<N>, N>
+where
+    N: Cell,
+{
+    /// Creates a computer that runs `rom` with empty in-memory input and output
+    /// queues, which are filled and drained by the caller between steps through
+    /// `input_mut` and `output_mut`.
+    pub fn new<R>(rom: R) -> Computer<VecDeque<N>, N>
     where
-        R: AsRef<[i64]>,
+        R: AsRef<[N]>,
     {
         Self {
             pc: 0,
             rb: 0,
-            ram: rom.as_ref().to_vec(),
+            ram: Ram::new(rom.as_ref().to_vec()),
+            program_len: rom.as_ref().len(),
             state: StateInternal::Executing,
+            last_state: None,
             input: VecDeque::default(),
             output: VecDeque::default(),
+            hook: None,
+            input_fn: None,
+            output_fn: None,
+            label: None,
+            profile: None,
+            dump_on_error: false,
+            input_timeout: None,
+            checkpoints: HashMap::default(),
         }
     }
+
+    /// Like `new`, but with `inputs` already waiting in the input queue.
+    pub fn with_input<R, I>(rom: R, inputs: I) -> Computer<VecDeque<N>, N>
+    where
+        R: AsRef<[N]>,
+        I: IntoIterator<Item = N>,
+    {
+        let mut computer = Self::new(rom);
+        computer.input.extend(inputs);
+        computer
+    }
+
+    /// Moves all pending outputs onto the end of `buf`, leaving the output queue empty.
+    /// Lets hot loops reuse one buffer instead of allocating a new `Vec` every time.
+    pub fn output_drain_into(&mut self, buf: &mut Vec<N>) {
+        buf.extend(self.output.drain(..));
+    }
+
+    /// Saves the computer's current state (memory, registers, and pending inputs and
+    /// outputs) under `name`, replacing any earlier checkpoint with the same name.
+    /// Checkpoints are meant to be taken between steps, typically right after an
+    /// output, so that a speculative branch of exploration can be undone later.
+    pub fn checkpoint(&mut self, name: &str) {
+        let snapshot = ComputerSnapshot {
+            pc: self.pc,
+            rb: self.rb,
+            ram: self.ram.clone(),
+            state: self.state,
+            last_state: self.last_state,
+            input: self.input.clone(),
+            output: self.output.clone(),
+        };
+        self.checkpoints.insert(name.to_string(), snapshot);
+    }
+
+    /// Restores the state saved by the checkpoint called `name`. The checkpoint is
+    /// kept, so it can be rolled back to again.
+    pub fn rollback(&mut self, name: &str) -> Result<(), Error> {
+        let snapshot = match self.checkpoints.get(name) {
+            Some(snapshot) => snapshot.clone(),
+            None => bail!("No checkpoint named {:?}.", name),
+        };
+        self.pc = snapshot.pc;
+        self.rb = snapshot.rb;
+        self.ram = snapshot.ram;
+        self.state = snapshot.state;
+        self.last_state = snapshot.last_state;
+        self.input = snapshot.input;
+        self.output = snapshot.output;
+        Ok(())
+    }
 }
 
-impl Computer<Channel<i64>> {
-    pub fn new<R>(rom: R, input: Channel<i64>, output: Channel<i64>) -> Computer<Channel<i64>>
+impl<const N: usize> Computer<RingQueue<N>> {
+    /// Like `Computer::<VecDeque<_>>::new`, but with fixed-capacity queues.
+    pub fn new<R>(rom: R) -> Computer<RingQueue<N>>
     where
         R: AsRef<[i64]>,
     {
         Self {
             pc: 0,
             rb: 0,
-            ram: rom.as_ref().to_vec(),
+            ram: Ram::new(rom.as_ref().to_vec()),
+            program_len: rom.as_ref().len(),
+            state: StateInternal::Executing,
+            last_state: None,
+            input: RingQueue::default(),
+            output: RingQueue::default(),
+            hook: None,
+            input_fn: None,
+            output_fn: None,
+            label: None,
+            profile: None,
+            dump_on_error: false,
+            input_timeout: None,
+            checkpoints: HashMap::default(),
+        }
+    }
+}
+
+impl<N> Computer<Channel<N>, N>
+where
+    N: Cell,
+{
+    /// Creates a computer that runs `rom`, reading from `input` and writing to
+    /// `output`. Keep clones of the channels (or the halves from `into_parts`) to
+    /// talk to the computer once it has been moved onto its own thread, e.g.
+    ///
+    /// ```
+    /// use std::thread;
+    ///
+    /// use aoc2019::{Channel, ComputerMT, Rom};
+    ///
+    /// // Outputs its input doubled.
+    /// let rom = Rom::from_reader("3,0,1,0,0,0,4,0,99".as_bytes())?;
+    /// let (input, output) = (Channel::default(), Channel::default());
+    /// let (sender, _) = input.clone().into_parts();
+    /// let (_, receiver) = output.clone().into_parts();
+    /// let handle = thread::spawn(move || ComputerMT::new(rom, input, output).run());
+    /// sender.send(21).unwrap();
+    /// assert_eq!(receiver.recv().unwrap(), 42);
+    /// handle.join().unwrap()?;
+    /// # Ok::<(), aoc2019::Error>(())
+    /// ```
+    pub fn new<R>(rom: R, input: Channel<N>, output: Channel<N>) -> Computer<Channel<N>, N>
+    where
+        R: AsRef<[N]>,
+    {
+        Self {
+            pc: 0,
+            rb: 0,
+            ram: Ram::new(rom.as_ref().to_vec()),
+            program_len: rom.as_ref().len(),
             state: StateInternal::Executing,
+            last_state: None,
             input,
             output,
+            hook: None,
+            input_fn: None,
+            output_fn: None,
+            label: None,
+            profile: None,
+            dump_on_error: false,
+            input_timeout: None,
+            checkpoints: HashMap::default(),
         }
     }
 }
 
-impl<Q> Computer<Q>
+impl<Q, N> Computer<Q, N>
 where
-    Q: Queue,
+    Q: Queue<N>,
+    N: Cell,
 {
+    /// Runs the computer until it halts. Needing input that isn't there is an error:
+    /// `Error::Timeout` if the input queue gave up waiting for it, and
+    /// `Error::NeedsInput` otherwise. A labeled computer's errors are wrapped in
+    /// `Error::Labeled`.
     pub fn run(&mut self) -> Result<(), Error> {
+        match (self.run_unlabeled(), &self.label) {
+            (Err(e), Some(label)) => Err(Error::Labeled {
+                label: label.clone(),
+                source: Box::new(e),
+            }),
+            (result, _) => result,
+        }
+    }
+
+    fn run_unlabeled(&mut self) -> Result<(), Error> {
         loop {
             match self.step()? {
                 State::Done => return Ok(()),
                 State::HasOutput => (),
-                State::NeedsInput => bail!("Needs input."),
+                State::NeedsInput => {
+                    return Err(match self.input_timeout.take() {
+                        Some(duration) => Error::Timeout { duration },
+                        None => Error::NeedsInput,
+                    })
+                }
             }
         }
     }
 
     pub fn step(&mut self) -> Result<State, Error> {
+        match self.step_inner() {
+            Ok(state) => {
+                self.last_state = Some(state);
+                Ok(state)
+            }
+            Err(e) if self.dump_on_error => Err(Error::CoreDump {
+                dump: self.core_dump(),
+                source: Box::new(e),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn step_inner(&mut self) -> Result<State, Error> {
         loop {
             match self.state {
                 StateInternal::Done => return Ok(State::Done),
                 StateInternal::Executing => {
+                    if self.hook.is_some() {
+                        self.trace_instruction()?;
+                    }
                     let instruction = self.read_instruction()?;
-                    self.execute_instruction(instruction);
+                    self.execute_instruction(instruction)?;
+                }
+                StateInternal::NeedsInput { w } => {
+                    let val = match &self.input_fn {
+                        Some(input_fn) => (input_fn.0.lock().unwrap())()?,
+                        None => match self.input.dequeue() {
+                            Ok(val) => val,
+                            Err(Error::Timeout { duration }) => {
+                                self.input_timeout = Some(duration);
+                                return Ok(State::NeedsInput);
+                            }
+                            Err(_) => return Ok(State::NeedsInput),
+                        },
+                    };
+                    self.ram.write(w, val)?;
+                    self.input_timeout = None;
+                    self.state = StateInternal::Executing;
                 }
-                StateInternal::NeedsInput { w } => match self.input.dequeue() {
-                    Ok(val) => {
-                        self.ram.write(w, val);
-                        self.state = StateInternal::Executing;
-                    }
-                    Err(_) => return Ok(State::NeedsInput),
-                },
                 StateInternal::HasOutput => {
                     self.state = StateInternal::Executing;
                     return Ok(State::HasOutput);
@@ -89,102 +352,283 @@ where
         }
     }
 
-    fn read_instruction(&mut self) -> Result<Instruction, Error> {
-        let (opcode, mut modes) = self.ram.read_opcode(&mut self.pc)?;
+    fn trace_instruction(&self) -> Result<(), Error> {
+        let (instruction, _) = disassemble_at(&self.ram, self.pc)?;
+        if let Some(hook) = &self.hook {
+            (hook.0.lock().unwrap())(self.pc, &instruction);
+        }
+        Ok(())
+    }
+
+    /// Decodes the instruction at the program counter and moves past it. The program
+    /// counter is left alone if decoding fails, so it still points at the culprit.
+    fn read_instruction(&mut self) -> Result<Instruction<N>, Error> {
+        let mut pc = self.pc;
+        let (opcode, mut modes) = self.ram.read_opcode(&mut pc)?;
+        if let Some(profile) = &mut self.profile {
+            *profile.entry(opcode).or_default() += 1;
+        }
         let instruction = match opcode {
             1 => Instruction::Add {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                b: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                w: self.ram.read_ptr(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                b: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                w: self.ram.read_ptr(&mut modes, self.rb, &mut pc)?,
             },
             2 => Instruction::Multiply {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                b: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                w: self.ram.read_ptr(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                b: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                w: self.ram.read_ptr(&mut modes, self.rb, &mut pc)?,
             },
             3 => Instruction::Input {
-                w: self.ram.read_ptr(&mut modes, self.rb, &mut self.pc)?,
+                w: self.ram.read_ptr(&mut modes, self.rb, &mut pc)?,
             },
             4 => Instruction::Output {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
             },
             5 => Instruction::JumpIfTrue {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                p: self.ram.read_unsigned(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                p: self.ram.read_unsigned(&mut modes, self.rb, &mut pc)?,
             },
             6 => Instruction::JumpIfFalse {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                p: self.ram.read_unsigned(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                p: self.ram.read_unsigned(&mut modes, self.rb, &mut pc)?,
             },
             7 => Instruction::LessThan {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                b: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                w: self.ram.read_ptr(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                b: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                w: self.ram.read_ptr(&mut modes, self.rb, &mut pc)?,
             },
             8 => Instruction::Equals {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                b: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
-                w: self.ram.read_ptr(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                b: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
+                w: self.ram.read_ptr(&mut modes, self.rb, &mut pc)?,
             },
             9 => Instruction::RelativeBase {
-                a: self.ram.read_signed(&mut modes, self.rb, &mut self.pc)?,
+                a: self.ram.read_signed(&mut modes, self.rb, &mut pc)?,
             },
             99 => Instruction::Halt,
-            _ => bail!("Unrecognized opcode {}", opcode),
+            _ => return Err(Error::Opcode(opcode)),
         };
+        self.pc = pc;
         Ok(instruction)
     }
 
-    fn execute_instruction(&mut self, instruction: Instruction) {
+    fn execute_instruction(&mut self, instruction: Instruction<N>) -> Result<(), Error> {
         match instruction {
             Instruction::Add { a, b, w } => {
-                self.ram.write(w, a + b);
+                self.ram.write(w, a + b)?;
             }
             Instruction::Multiply { a, b, w } => {
-                self.ram.write(w, a * b);
+                self.ram.write(w, a * b)?;
             }
             Instruction::Input { w } => {
                 self.state = StateInternal::NeedsInput { w };
-                return;
+                return Ok(());
             }
             Instruction::Output { a } => {
-                self.output.enqueue(a);
+                match &self.output_fn {
+                    Some(output_fn) => (output_fn.0.lock().unwrap())(a),
+                    None => self.output.enqueue(a)?,
+                }
                 self.state = StateInternal::HasOutput;
-                return;
+                return Ok(());
             }
             Instruction::JumpIfTrue { a, p } => {
-                if a != 0 {
+                if a != N::default() {
                     self.pc = p;
                 }
             }
             Instruction::JumpIfFalse { a, p } => {
-                if a == 0 {
+                if a == N::default() {
                     self.pc = p;
                 }
             }
             Instruction::LessThan { a, b, w } => {
                 if a < b {
-                    self.ram.write(w, 1);
+                    self.ram.write(w, N::from(1))?;
                 } else {
-                    self.ram.write(w, 0);
+                    self.ram.write(w, N::default())?;
                 }
             }
             Instruction::Equals { a, b, w } => {
                 if a == b {
-                    self.ram.write(w, 1);
+                    self.ram.write(w, N::from(1))?;
                 } else {
-                    self.ram.write(w, 0);
+                    self.ram.write(w, N::default())?;
                 }
             }
             Instruction::RelativeBase { a } => {
-                self.rb += a;
+                self.rb += to_i64(a)?;
             }
             Instruction::Halt => {
                 self.state = StateInternal::Done;
-                return;
+                return Ok(());
             }
         }
         self.state = StateInternal::Executing;
+        Ok(())
+    }
+
+    /// Runs the computer until it produces a single output, returning `None` instead
+    /// if it halts first. Needing input that isn't there is an error, as is having an
+    /// output sink, which would take the output before it could be returned.
+    pub fn run_to_output(&mut self) -> Result<Option<N>, Error> {
+        if self.output_fn.is_some() {
+            bail!("Cannot run to an output when outputs go to an output sink.");
+        }
+        match self.step()? {
+            State::HasOutput => Ok(Some(self.output.dequeue()?)),
+            State::Done => Ok(None),
+            State::NeedsInput => Err(Error::NeedsInput),
+        }
+    }
+
+    /// Runs the computer until it needs input it doesn't have or halts, returning the
+    /// outputs produced along the way (none, if they went to an output sink) and
+    /// whether it halted.
+    pub fn execute_until_input(&mut self) -> Result<(Vec<N>, bool), Error> {
+        let mut outputs = Vec::new();
+        loop {
+            match self.step()? {
+                State::HasOutput => outputs.extend(self.take_output()?),
+                State::NeedsInput => return Ok((outputs, false)),
+                State::Done => return Ok((outputs, true)),
+            }
+        }
+    }
+
+    /// Takes the output `step` just reported, unless it went to the output sink.
+    fn take_output(&mut self) -> Result<Option<N>, Error> {
+        match self.output_fn {
+            Some(_) => Ok(None),
+            None => self.output.dequeue().map(Some),
+        }
+    }
+
+    /// Enqueues every value in `vals` onto the input queue, in order.
+    pub fn feed(&mut self, vals: &[N]) -> Result<(), Error> {
+        for &val in vals {
+            self.input.enqueue(val)?;
+        }
+        Ok(())
+    }
+
+    /// Drains every output available right now into a `Vec`, oldest first.
+    pub fn collect_outputs(&mut self) -> Vec<N> {
+        let mut outputs = Vec::with_capacity(self.output.len());
+        while let Some(val) = self.output.try_dequeue() {
+            outputs.push(val);
+        }
+        outputs
+    }
+
+    /// Returns an iterator that runs the computer, yielding each output as it is
+    /// produced, until the program halts. Errors, including needing input that isn't
+    /// there, are yielded once and end the iteration.
+    pub fn outputs(&mut self) -> Outputs<'_, Q, N> {
+        Outputs {
+            computer: self,
+            done: false,
+        }
+    }
+
+    /// Enqueues a rendered grid (rows separated by `\n`) as ASCII input, ending every
+    /// row, including the last, with a newline.
+    pub fn input_from_ascii_art(&mut self, grid: &str) -> Result<(), Error> {
+        for line in grid.lines() {
+            for b in line.bytes().chain(Some(b'\n')) {
+                self.input.enqueue(N::from(b as i64))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Names the computer in the errors `run` returns, which would otherwise be hard to
+    /// place when several computers are talking to each other. See `Error::Labeled`.
+    pub fn set_label(&mut self, label: impl Into<String>) {
+        self.label = Some(label.into());
+    }
+
+    /// Describes the computer's registers, state, queues, and memory, eight cells to
+    /// a line, for working out what went wrong after the fact.
+    pub fn core_dump(&self) -> String {
+        let mut s = format!(
+            "pc: {}\nrb: {}\nlast state: {}\ninputs queued: {}\noutputs queued: {}\nmemory:",
+            self.pc,
+            self.rb,
+            match self.state() {
+                Some(state) => state.to_string(),
+                None => "not stepped yet".to_string(),
+            },
+            self.input.len(),
+            self.output.len()
+        );
+        for (i, cells) in self.ram.chunks(8).enumerate() {
+            s.push_str(&format!("\n{:06x}:", i * 8));
+            for cell in cells {
+                s.push_str(&format!(" {}", cell));
+            }
+        }
+        s
+    }
+
+    /// Whether errors from `step` and `run` should be wrapped in `Error::CoreDump`,
+    /// along with a `core_dump` of the computer as it was when they happened. Off by
+    /// default.
+    pub fn set_dump_on_error(&mut self, enabled: bool) {
+        self.dump_on_error = enabled;
+    }
+
+    /// Starts counting how many times each opcode is executed, for finding out what
+    /// a slow program spends its time on. Counting is off unless this is called.
+    pub fn enable_profiling(&mut self) {
+        self.profile.get_or_insert_with(Profile::default);
+    }
+
+    /// The opcode counts since `enable_profiling` was called, if it has been.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Calls `f` with the program counter and disassembly of every instruction this
+    /// computer executes from now on. Clones of the computer share the same hook.
+    pub fn set_trace(&mut self, f: TraceFn) {
+        self.hook = Some(TraceHook(Arc::new(Mutex::new(f))));
+    }
+
+    /// Calls `f` for the value of every input instruction from now on, instead of
+    /// taking values off the input queue, so a driver can decide each input as it is
+    /// needed. An error from `f` is returned by `step`. Clones of the computer share
+    /// the same callback.
+    pub fn set_input_fn(&mut self, f: InputFn<N>) {
+        self.input_fn = Some(InputHook(Arc::new(Mutex::new(f))));
+    }
+
+    /// Passes every output from now on to `f` instead of the output queue, so a
+    /// long-running program's outputs can be consumed as they are produced rather
+    /// than piling up. `step` still reports `State::HasOutput` after each one, but
+    /// there is nothing to dequeue, so `execute_until_input` returns no outputs and
+    /// `run_to_output` (and with it `outputs`) is an error. Clones of the computer
+    /// share the same sink.
+    pub fn set_output_sink(&mut self, f: OutputFn<N>) {
+        self.output_fn = Some(OutputHook(Arc::new(Mutex::new(f))));
+    }
+
+    /// Number of outputs waiting in the output queue.
+    pub fn output_len(&self) -> usize {
+        self.output.len()
+    }
+
+    /// Whether the computer is stopped at an input instruction because its input
+    /// queue had nothing to give it.
+    pub fn is_waiting_for_input(&self) -> bool {
+        matches!(self.state, StateInternal::NeedsInput { .. })
+    }
+
+    /// The state `step` last returned, or `None` if it hasn't been stepped yet.
+    /// Unlike `step`, this has no side effects.
+    pub fn state(&self) -> Option<State> {
+        self.last_state
     }
 
     pub fn input_mut(&mut self) -> &mut Q {
@@ -195,17 +639,243 @@ where
         &mut self.output
     }
 
-    #[cfg(test)]
-    pub(crate) fn ram(&self) -> &[i64] {
+    /// Replaces the program in memory with `rom` and starts over from its beginning,
+    /// discarding any pending inputs and outputs. Memory's existing allocation is
+    /// reused, which makes this cheaper than building a new computer when running
+    /// many programs in turn. Settings such as the memory limit and trace callback
+    /// are kept.
+    pub fn load(&mut self, rom: &[N]) {
+        self.ram.cells.clear();
+        self.ram.cells.extend_from_slice(rom);
+        self.program_len = rom.len();
+        self.pc = 0;
+        self.rb = 0;
+        self.state = StateInternal::Executing;
+        self.last_state = None;
+        while self.input.try_dequeue().is_some() {}
+        while self.output.try_dequeue().is_some() {}
+    }
+
+    /// Returns a computer with a copy of this one's memory, as it stands, that starts
+    /// executing from the beginning with empty queues of its own. Unlike `clone`, the
+    /// program counter, relative base, queues, and checkpoints are not copied; unlike
+    /// `load`, this computer is left alone.
+    pub fn clone_fresh(&self) -> Self
+    where
+        Q: Default,
+    {
+        Self {
+            pc: 0,
+            rb: 0,
+            ram: self.ram.clone(),
+            program_len: self.program_len,
+            state: StateInternal::Executing,
+            last_state: None,
+            input: Q::default(),
+            output: Q::default(),
+            hook: self.hook.clone(),
+            input_fn: self.input_fn.clone(),
+            output_fn: self.output_fn.clone(),
+            label: self.label.clone(),
+            profile: self.profile.as_ref().map(|_| Profile::default()),
+            dump_on_error: self.dump_on_error,
+            input_timeout: None,
+            checkpoints: HashMap::default(),
+        }
+    }
+
+    /// Makes any instruction that reads or writes at or beyond `cells` an error,
+    /// instead of growing memory to fit the pointer, so that a runaway program fails
+    /// rather than allocating without bound. Reads and writes made through
+    /// `Computer::read` and `Computer::write` are not limited.
+    pub fn with_memory_limit(mut self, cells: usize) -> Self {
+        self.ram.limit = Some(cells);
+        self
+    }
+
+    /// Current size of memory, including any scratch space allocated past the end of
+    /// the program by reads or writes to high addresses.
+    pub fn ram_len(&self) -> usize {
+        self.ram.len()
+    }
+
+    /// Length of the program originally loaded into memory.
+    pub fn program_len(&self) -> usize {
+        self.program_len
+    }
+
+    /// The computer's whole memory as it stands, including anything beyond the end
+    /// of the program that has been written to while running.
+    pub fn memory(&self) -> &[N] {
         &self.ram
     }
 
-    pub fn read(&mut self, ptr: u64) -> i64 {
-        self.ram.read(ptr)
+    pub fn read(&mut self, ptr: u64) -> N {
+        self.ram.peek(ptr)
+    }
+
+    pub fn write(&mut self, ptr: u64, val: N) {
+        self.ram.poke(ptr, val)
+    }
+}
+
+/// Builds a computer from `rom` with addresses 1 and 2 replaced by `noun` and `verb`,
+/// as in day 2.
+pub fn apply_noun_verb(rom: &Rom, noun: i64, verb: i64) -> Result<ComputerST, Error> {
+    let mut rom = rom.clone();
+    rom.patch(1, noun)?;
+    rom.patch(2, verb)?;
+    Ok(ComputerST::new(&rom))
+}
+
+/// Runs `rom` interactively. Each output is written to `output` on its own line.
+/// Whenever the program needs input, a line is read from `input`; if it is a list
+/// of integers (separated by commas or whitespace) they are fed to the program,
+/// otherwise the line is fed as ASCII codes followed by a newline.
+pub fn repl<R, W>(rom: &Rom, mut input: R, mut output: W) -> Result<(), Error>
+where
+    R: io::BufRead,
+    W: io::Write,
+{
+    let mut computer = ComputerST::new(rom);
+    let mut line = String::new();
+    loop {
+        match computer.step()? {
+            State::Done => return Ok(()),
+            State::HasOutput => {
+                if let Some(val) = computer.take_output()? {
+                    writeln!(output, "{}", val)?;
+                }
+            }
+            State::NeedsInput => {
+                output.flush()?;
+                line.clear();
+                if input.read_line(&mut line)? == 0 {
+                    bail!("Program needs input, but there is no more to read.");
+                }
+                let line = line.trim_end_matches(&['\r', '\n'][..]);
+                let ints = line
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|s| !s.is_empty())
+                    .map(|s| s.parse::<i64>())
+                    .collect::<Result<Vec<_>, _>>();
+                match ints {
+                    Ok(ints) if !ints.is_empty() => {
+                        for val in ints {
+                            computer.input.enqueue(val)?;
+                        }
+                    }
+                    _ => {
+                        for b in line.bytes().chain(std::iter::once(b'\n')) {
+                            computer.input.enqueue(b as i64)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A per-computer tracing callback. See `Computer::set_trace`.
+#[derive(Clone)]
+struct TraceHook(Arc<Mutex<TraceFn>>);
+
+impl fmt::Debug for TraceHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TraceHook")
+    }
+}
+
+/// Callbacks for a day's solver to install on every computer it creates, so that
+/// the caller can observe computers it has no other way to reach. The default
+/// installs nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Hooks {
+    trace: Option<TraceHook>,
+}
+
+impl Hooks {
+    /// Hooks that give every computer `f` as its trace callback, as if by
+    /// `Computer::set_trace`. The computers share `f` with each other, even across
+    /// threads.
+    pub fn with_trace(f: TraceFn) -> Self {
+        Self {
+            trace: Some(TraceHook(Arc::new(Mutex::new(f)))),
+        }
+    }
+
+    /// Installs the hooks on `computer`.
+    pub fn apply<Q, N>(&self, computer: &mut Computer<Q, N>)
+    where
+        Q: Queue<N>,
+        N: Cell,
+    {
+        if let Some(trace) = &self.trace {
+            computer.hook = Some(trace.clone());
+        }
+    }
+}
+
+/// A per-computer input callback. See `Computer::set_input_fn`.
+struct InputHook<N>(Arc<Mutex<InputFn<N>>>);
+
+impl<N> Clone for InputHook<N> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<N> fmt::Debug for InputHook<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InputHook")
+    }
+}
+
+/// A per-computer output callback. See `Computer::set_output_sink`.
+struct OutputHook<N>(Arc<Mutex<OutputFn<N>>>);
+
+impl<N> Clone for OutputHook<N> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<N> fmt::Debug for OutputHook<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OutputHook")
     }
+}
+
+/// Iterator over the outputs of a `Computer`. See `Computer::outputs`.
+#[derive(Debug)]
+pub struct Outputs<'a, Q, N = i64> {
+    computer: &'a mut Computer<Q, N>,
+    done: bool,
+}
+
+impl<'a, Q, N> Iterator for Outputs<'a, Q, N>
+where
+    Q: Queue<N>,
+    N: Cell,
+{
+    type Item = Result<N, Error>;
 
-    pub fn write(&mut self, ptr: u64, val: i64) {
-        self.ram.write(ptr, val)
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = match self.computer.run_to_output() {
+            Ok(Some(val)) => Ok(val),
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => Err(e),
+        };
+        if res.is_err() {
+            self.done = true;
+        }
+        Some(res)
     }
 }
 
@@ -213,59 +883,247 @@ where
 pub struct Channel<T> {
     sender: Sender<T>,
     receiver: Receiver<T>,
+    timeout: Duration,
+    // A value taken off the channel by `peek` but not yet dequeued. It's shared by
+    // every clone of the channel, but not by a receiver taken from `into_parts`.
+    peeked: Arc<Mutex<Option<T>>>,
 }
 
 impl<T> Default for Channel<T> {
     fn default() -> Self {
-        let (sender, receiver) = channel::bounded(1024);
-        Self { sender, receiver }
+        Self::with_timeout(Duration::from_secs(5))
     }
 }
 
 impl<T> Channel<T> {
+    /// Creates a channel whose `dequeue` gives up with `Error::Timeout` after waiting
+    /// `timeout` for a value.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        let (sender, receiver) = channel::bounded(1024);
+        Self {
+            sender,
+            receiver,
+            timeout,
+            peeked: Arc::new(Mutex::new(None)),
+        }
+    }
+
     pub fn into_parts(self) -> (Sender<T>, Receiver<T>) {
         (self.sender, self.receiver)
     }
 }
 
-impl Queue for Channel<i64> {
-    fn enqueue(&mut self, val: i64) {
-        self.sender.send(val).unwrap()
+impl<N> Queue<N> for Channel<N>
+where
+    N: Cell,
+{
+    fn enqueue(&mut self, val: N) -> Result<(), Error> {
+        self.sender
+            .send(val)
+            .map_err(|_| error!("Attempted to push value onto channel, but it was disconnected."))
     }
 
-    fn dequeue(&mut self) -> Result<i64, Error> {
+    fn dequeue(&mut self) -> Result<N, Error> {
         use crossbeam::channel::RecvTimeoutError;
-        match self.receiver.recv_timeout(Duration::from_secs(5)) {
+        if let Some(val) = self.peeked.lock().unwrap().take() {
+            return Ok(val);
+        }
+        match self.receiver.recv_timeout(self.timeout) {
             Ok(val) => Ok(val),
             Err(e) => match e {
-                RecvTimeoutError::Timeout => {
-                    bail!("Attempted to pop value off channel, but timed out.")
-                }
+                RecvTimeoutError::Timeout => Err(Error::Timeout {
+                    duration: self.timeout,
+                }),
                 RecvTimeoutError::Disconnected => unreachable!(),
             },
         }
     }
+
+    fn try_dequeue(&mut self) -> Option<N> {
+        if let Some(val) = self.peeked.lock().unwrap().take() {
+            return Some(val);
+        }
+        self.receiver.try_recv().ok()
+    }
+
+    fn peek(&self) -> Option<N> {
+        let mut peeked = self.peeked.lock().unwrap();
+        if peeked.is_none() {
+            *peeked = self.receiver.try_recv().ok();
+        }
+        *peeked
+    }
+
+    fn len(&self) -> usize {
+        self.receiver.len() + self.peeked.lock().unwrap().is_some() as usize
+    }
+}
+
+/// Each opcode with its mnemonic and number of parameters.
+const OPCODES: [(u64, &str, u64); 10] = [
+    (1, "ADD", 3),
+    (2, "MUL", 3),
+    (3, "IN", 1),
+    (4, "OUT", 1),
+    (5, "JT", 2),
+    (6, "JF", 2),
+    (7, "LT", 3),
+    (8, "EQ", 3),
+    (9, "ARB", 1),
+    (99, "HALT", 0),
+];
+
+/// Disassembles the single instruction starting at `pc`, rendering position mode
+/// operands as `[addr]`, immediate mode operands as bare numbers, and relative mode
+/// operands as `rel offset`. Also returns the width of the instruction in cells.
+fn disassemble_at<N>(ram: &[N], pc: u64) -> Result<(String, u64), Error>
+where
+    N: Cell,
+{
+    let peek = |ptr: u64| ram.get(ptr as usize).copied().unwrap_or_default();
+
+    let n = to_i64(peek(pc))?;
+    if n < 0 {
+        bail!("Read negative opcode {}, which is not allowed.", n);
+    }
+    let opcode = n as u64 % 100;
+    let mut modes = Modes(n as u64 / 100);
+    let (mnemonic, nparams) = match OPCODES.iter().find(|(op, _, _)| *op == opcode) {
+        Some(&(_, mnemonic, nparams)) => (mnemonic, nparams),
+        None => return Err(Error::Opcode(opcode)),
+    };
+
+    let mut s = mnemonic.to_string();
+    for i in 0..nparams {
+        let val = peek(pc + 1 + i);
+        s.push_str(if i == 0 { " " } else { ", " });
+        match modes.next().unwrap()? {
+            Mode::Position => s.push_str(&format!("[{}]", val)),
+            Mode::Immediate => s.push_str(&val.to_string()),
+            Mode::Relative => s.push_str(&format!("rel {}", val)),
+        }
+    }
+    Ok((s, 1 + nparams))
+}
+
+/// Disassembles a whole program into lines of the form `pc: instruction`. Cells
+/// that don't decode as an instruction (e.g. data stored after the code) are
+/// rendered one per line as `pc: DATA value`.
+pub fn disassemble<N>(rom: &Rom<N>) -> String
+where
+    N: Cell,
+{
+    let mut s = String::new();
+    let mut pc = 0;
+    while (pc as usize) < rom.len() {
+        let width = match disassemble_at(rom, pc) {
+            Ok((instruction, width)) => {
+                s.push_str(&format!("{}: {}\n", pc, instruction));
+                width
+            }
+            Err(_) => {
+                s.push_str(&format!("{}: DATA {}\n", pc, rom[pc as usize]));
+                1
+            }
+        };
+        pc += width;
+    }
+    s
+}
+
+/// Assembles a program written in the syntax `disassemble` produces: one instruction
+/// per line, as a mnemonic followed by comma-separated operands written `[addr]` for
+/// position mode, `rel offset` for relative mode, and as a bare number for immediate
+/// mode, or `DATA value` for a raw cell. Lines may start with a `pc:` label, which
+/// is ignored, and blank lines are skipped. For any program whose instructions all
+/// fit within it, `assemble(&disassemble(&rom))` gives back `rom`.
+pub fn assemble(src: &str) -> Result<Rom, Error> {
+    let mut cells = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        assemble_line(line, &mut cells)
+            .map_err(|e| error!("Failed to assemble line {}: {}", i + 1, e))?;
+    }
+    Ok(Rom(cells))
+}
+
+fn assemble_line(line: &str, cells: &mut Vec<i64>) -> Result<(), Error> {
+    let mut line = line.trim();
+    if let Some((label, rest)) = line.split_once(':') {
+        if label.trim().parse::<u64>().is_err() {
+            bail!("Invalid label {:?}.", label.trim());
+        }
+        line = rest.trim();
+    }
+    if line.is_empty() {
+        return Ok(());
+    }
+
+    let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+        None => (line, ""),
+    };
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    if mnemonic == "DATA" {
+        cells.push(operands.parse()?);
+        return Ok(());
+    }
+    let (opcode, nparams) = match OPCODES.iter().find(|(_, m, _)| *m == mnemonic) {
+        Some(&(opcode, _, nparams)) => (opcode, nparams),
+        None => bail!("Unrecognized mnemonic {:?}.", mnemonic),
+    };
+
+    let operands = if operands.is_empty() {
+        Vec::new()
+    } else {
+        operands.split(',').map(str::trim).collect::<Vec<_>>()
+    };
+    if operands.len() as u64 != nparams {
+        bail!(
+            "{} takes {} operands, but got {}.",
+            mnemonic,
+            nparams,
+            operands.len()
+        );
+    }
+
+    let mut instruction = opcode as i64;
+    let mut params = Vec::with_capacity(operands.len());
+    for (i, operand) in operands.iter().enumerate() {
+        let (mode, val) =
+            if let Some(addr) = operand.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                (Mode::Position, addr.trim())
+            } else if let Some(offset) = operand.strip_prefix("rel ") {
+                (Mode::Relative, offset.trim())
+            } else {
+                (Mode::Immediate, *operand)
+            };
+        instruction += mode as i64 * 10i64.pow(i as u32 + 2);
+        params.push(val.parse::<i64>()?);
+    }
+    cells.push(instruction);
+    cells.extend(params);
+    Ok(())
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum Instruction {
-    Add { a: i64, b: i64, w: u64 },
-    Multiply { a: i64, b: i64, w: u64 },
+enum Instruction<N> {
+    Add { a: N, b: N, w: u64 },
+    Multiply { a: N, b: N, w: u64 },
     Input { w: u64 },
-    Output { a: i64 },
-    JumpIfTrue { a: i64, p: u64 },
-    JumpIfFalse { a: i64, p: u64 },
-    LessThan { a: i64, b: i64, w: u64 },
-    Equals { a: i64, b: i64, w: u64 },
-    RelativeBase { a: i64 },
+    Output { a: N },
+    JumpIfTrue { a: N, p: u64 },
+    JumpIfFalse { a: N, p: u64 },
+    LessThan { a: N, b: N, w: u64 },
+    Equals { a: N, b: N, w: u64 },
+    RelativeBase { a: N },
     Halt,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum Mode {
-    Position,
-    Immediate,
-    Relative,
+    Position = 0,
+    Immediate = 1,
+    Relative = 2,
 }
 
 impl TryFrom<u64> for Mode {
@@ -275,7 +1133,7 @@ impl TryFrom<u64> for Mode {
             0 => Mode::Position,
             1 => Mode::Immediate,
             2 => Mode::Relative,
-            _ => bail!("Unrecognized addressing mode {}", n),
+            _ => return Err(Error::AddressingMode(n)),
         };
         Ok(output)
     }
@@ -301,12 +1159,15 @@ impl Iterator for Modes {
     }
 }
 
-trait Memory {
-    fn read(&mut self, ptr: u64) -> i64;
-    fn write(&mut self, ptr: u64, val: i64);
+trait Memory<N>
+where
+    N: Cell,
+{
+    fn read(&mut self, ptr: u64) -> Result<N, Error>;
+    fn write(&mut self, ptr: u64, val: N) -> Result<(), Error>;
 
     fn read_opcode(&mut self, pc: &mut u64) -> Result<(u64, Modes), Error> {
-        let n = self.read(*pc);
+        let n = to_i64(self.read(*pc)?)?;
         *pc += 1;
         if n < 0 {
             bail!("Read negative opcode {}, which is not allowed.", n);
@@ -318,30 +1179,28 @@ trait Memory {
         Ok((opcode, modes))
     }
 
-    fn read_signed(&mut self, modes: &mut Modes, rb: i64, pc: &mut u64) -> Result<i64, Error> {
-        let mut val = self.read(*pc);
+    fn read_signed(&mut self, modes: &mut Modes, rb: i64, pc: &mut u64) -> Result<N, Error> {
+        let val = self.read(*pc)?;
         *pc += 1;
 
         let mode = modes.next().unwrap()?;
         match mode {
             Mode::Immediate => Ok(val),
             Mode::Position | Mode::Relative => {
+                let mut val = to_i64(val)?;
                 if mode == Mode::Relative {
                     val += rb;
                 }
                 if val < 0 {
-                    bail!(
-                        "Encountered negative pointer {}, which is not allowed.",
-                        val
-                    );
+                    return Err(Error::NegativePointer(val));
                 }
-                let val2 = self.read(val as u64);
+                let val2 = self.read(val as u64)?;
                 Ok(val2)
             }
         }
     }
     fn read_unsigned(&mut self, modes: &mut Modes, rb: i64, pc: &mut u64) -> Result<u64, Error> {
-        let val = self.read_signed(modes, rb, pc)?;
+        let val = to_i64(self.read_signed(modes, rb, pc)?)?;
         if val < 0 {
             bail!("Reading unsigned integer but found negative value {}.", val);
         }
@@ -349,7 +1208,7 @@ trait Memory {
     }
 
     fn read_ptr(&mut self, modes: &mut Modes, rb: i64, pc: &mut u64) -> Result<u64, Error> {
-        let val = self.read(*pc);
+        let val = to_i64(self.read(*pc)?)?;
         *pc += 1;
 
         let mode = modes.next().unwrap()?;
@@ -359,82 +1218,284 @@ trait Memory {
         };
 
         if val2 < 0 {
-            bail!(
-                "Encountered negative pointer {}, which is not allowed.",
-                val2
-            );
+            return Err(Error::NegativePointer(val2));
         }
         Ok(val2 as u64)
     }
 }
 
-impl Memory for Vec<i64> {
-    fn read(&mut self, ptr: u64) -> i64 {
-        if ptr as usize >= self.len() {
-            self.resize(ptr as usize + 1, 0);
+/// A computer's memory, which grows to fit whatever address is accessed unless it
+/// has been given a limit.
+#[derive(Clone, Debug)]
+struct Ram<N> {
+    cells: Vec<N>,
+    limit: Option<usize>,
+}
+
+impl<N> Ram<N>
+where
+    N: Cell,
+{
+    fn new(cells: Vec<N>) -> Self {
+        Self { cells, limit: None }
+    }
+
+    fn peek(&mut self, ptr: u64) -> N {
+        self.grow(ptr);
+        self.cells[ptr as usize]
+    }
+
+    fn poke(&mut self, ptr: u64, val: N) {
+        self.grow(ptr);
+        self.cells[ptr as usize] = val;
+    }
+
+    fn grow(&mut self, ptr: u64) {
+        if ptr as usize >= self.cells.len() {
+            self.cells.resize(ptr as usize + 1, N::default());
         }
-        *self.get(ptr as usize).unwrap()
     }
 
-    fn write(&mut self, ptr: u64, val: i64) {
-        if ptr as usize >= self.len() {
-            self.resize(ptr as usize + 1, 0);
+    fn check(&self, ptr: u64) -> Result<(), Error> {
+        match self.limit {
+            Some(limit) if ptr >= limit as u64 => bail!(
+                "Pointer {} is beyond the memory limit of {} cells.",
+                ptr,
+                limit
+            ),
+            _ => Ok(()),
         }
-        let reference = self.get_mut(ptr as usize).unwrap();
-        *reference = val;
+    }
+}
+
+impl<N> Deref for Ram<N> {
+    type Target = [N];
+    fn deref(&self) -> &Self::Target {
+        &self.cells
+    }
+}
+
+impl<N> Memory<N> for Ram<N>
+where
+    N: Cell,
+{
+    fn read(&mut self, ptr: u64) -> Result<N, Error> {
+        self.check(ptr)?;
+        Ok(self.peek(ptr))
+    }
+
+    fn write(&mut self, ptr: u64, val: N) -> Result<(), Error> {
+        self.check(ptr)?;
+        self.poke(ptr, val);
+        Ok(())
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct Rom(Vec<i64>);
+pub struct Rom<N = i64>(Vec<N>);
 
-impl Rom {
+impl<N> Rom<N>
+where
+    N: Cell,
+{
+    /// Reads a program of comma-separated values. Besides the usual single line, the
+    /// values may be spread over several lines (with or without a comma at the end of
+    /// each), and anything from a `#` to the end of its line is ignored, so programs
+    /// can be written out by hand with comments.
     pub fn from_reader<R>(mut reader: R) -> Result<Self, Error>
     where
         R: io::BufRead,
     {
         let mut buf = String::new();
         reader.read_to_string(&mut buf)?;
-        let vec = buf
-            .trim()
-            .split(',')
-            .map(|s| s.trim().parse::<i64>().map_err(Error::from))
-            .collect::<Result<Vec<_>, Error>>()?;
+        Self::parse(&buf)
+    }
+
+    /// Reads one program per line, skipping blank lines and `#` comment lines.
+    pub fn from_lines<R>(reader: R) -> Result<Vec<Self>, Error>
+    where
+        R: io::BufRead,
+    {
+        let mut roms = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rom = Self::parse(line)
+                .map_err(|e| error!("Failed to parse the program on line {}: {}", i + 1, e))?;
+            roms.push(rom);
+        }
+        Ok(roms)
+    }
+
+    /// Overwrites the cell at `addr` with `val`. Unlike a computer's memory, a ROM
+    /// doesn't grow, so `addr` must already be in the program.
+    pub fn patch(&mut self, addr: usize, val: N) -> Result<(), Error> {
+        match self.0.get_mut(addr) {
+            Some(cell) => *cell = val,
+            None => bail!(
+                "Cannot patch address {} of a program of length {}.",
+                addr,
+                self.0.len()
+            ),
+        }
+        Ok(())
+    }
+
+    /// Number of cells in the program.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the program has no cells. Parsed programs never do, but assembled
+    /// or hand-built ones can.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn parse(s: &str) -> Result<Self, Error> {
+        let mut vec = Vec::new();
+        for line in s.lines() {
+            let line = match line.find('#') {
+                Some(i) => &line[..i],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            let line = line.strip_suffix(',').unwrap_or(line);
+            for s in line.split(',') {
+                vec.push(s.trim().parse::<N>()?);
+            }
+        }
+        if vec.is_empty() {
+            bail!("Cannot parse an empty program.");
+        }
         Ok(Rom(vec))
     }
 }
 
-impl AsRef<[i64]> for Rom {
-    fn as_ref(&self) -> &[i64] {
+impl<N> AsRef<[N]> for Rom<N> {
+    fn as_ref(&self) -> &[N] {
         &self.0
     }
 }
 
-impl std::ops::Deref for Rom {
-    type Target = [i64];
+impl<N> Deref for Rom<N> {
+    type Target = [N];
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl std::ops::DerefMut for Rom {
+impl<N> std::ops::DerefMut for Rom<N> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
-pub trait Queue {
-    fn dequeue(&mut self) -> Result<i64, Error>;
-    fn enqueue(&mut self, val: i64);
+pub trait Queue<N = i64> {
+    fn dequeue(&mut self) -> Result<N, Error>;
+    fn enqueue(&mut self, val: N) -> Result<(), Error>;
+
+    /// Pops a value if one is available right now, returning `None` instead of
+    /// blocking or erroring when the queue is empty. The default is only appropriate
+    /// for queues whose `dequeue` never blocks.
+    fn try_dequeue(&mut self) -> Option<N> {
+        self.dequeue().ok()
+    }
+
+    /// The value `dequeue` would return next, without removing it; `None` if the
+    /// queue is empty right now.
+    fn peek(&self) -> Option<N>;
+
+    /// Number of values waiting in the queue right now.
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
-impl Queue for VecDeque<i64> {
-    fn dequeue(&mut self) -> Result<i64, Error> {
+impl<N> Queue<N> for VecDeque<N>
+where
+    N: Cell,
+{
+    fn dequeue(&mut self) -> Result<N, Error> {
         self.pop_front()
             .ok_or_else(|| error!("Attempted to pop something off the queue, but queue was empty"))
     }
-    fn enqueue(&mut self, val: i64) {
+    fn try_dequeue(&mut self) -> Option<N> {
+        self.pop_front()
+    }
+    fn enqueue(&mut self, val: N) -> Result<(), Error> {
         self.push_back(val);
+        Ok(())
+    }
+    fn peek(&self) -> Option<N> {
+        self.front().copied()
+    }
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+}
+
+/// A fixed-capacity `Queue` backed by an array, for when the most values that will
+/// ever be waiting at once is known and small. It never allocates; enqueueing onto
+/// a full queue is an error.
+#[derive(Copy, Clone, Debug)]
+pub struct RingQueue<const N: usize> {
+    buf: [i64; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> Default for RingQueue<N> {
+    fn default() -> Self {
+        Self {
+            buf: [0; N],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> Queue for RingQueue<N> {
+    fn dequeue(&mut self) -> Result<i64, Error> {
+        if self.len == 0 {
+            bail!("Attempted to pop something off the queue, but queue was empty");
+        }
+        let val = self.buf[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Ok(val)
+    }
+
+    fn enqueue(&mut self, val: i64) -> Result<(), Error> {
+        if self.len == N {
+            bail!(
+                "Attempted to push onto the queue, but it is already at its capacity of {}",
+                N
+            );
+        }
+        self.buf[(self.head + self.len) % N] = val;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<i64> {
+        if self.len == 0 {
+            None
+        } else {
+            Some(self.buf[self.head])
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
     }
 }
 
@@ -445,6 +1506,17 @@ pub enum State {
     HasOutput,
 }
 
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            State::Done => "Done",
+            State::NeedsInput => "NeedsInput",
+            State::HasOutput => "HasOutput",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum StateInternal {
     Done,
@@ -457,6 +1529,8 @@ enum StateInternal {
 mod tests {
     use super::*;
 
+    use crate::utils;
+
     #[test]
     fn test_computer() {
         let test_cases = &[
@@ -478,7 +1552,668 @@ mod tests {
                 .split(",")
                 .map(|s| s.trim().parse::<i64>().unwrap())
                 .collect::<Vec<_>>();
-            assert_eq!(computer.ram(), &expected_ram[..]);
+            assert_eq!(computer.memory(), &expected_ram[..]);
+        }
+    }
+
+    #[test]
+    fn test_memory() {
+        let rom = Rom::from_reader("1,9,10,3,2,3,11,0,99,30,40,50".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        computer.run().unwrap();
+        assert_eq!(computer.memory()[0], 3500);
+
+        // Writing past the end of the program grows memory.
+        let rom = Rom::from_reader("1101,2,3,20,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        computer.run().unwrap();
+        assert_eq!(computer.memory().len(), 21);
+        assert_eq!(computer.memory()[20], 5);
+    }
+
+    #[test]
+    fn test_disassemble_at() {
+        let ram = [1002i64, 4, 3, 4, 33, 204, -1, 99];
+        assert_eq!(
+            disassemble_at(&ram, 0).unwrap(),
+            ("MUL [4], 3, [4]".into(), 4)
+        );
+        assert_eq!(disassemble_at(&ram, 5).unwrap(), ("OUT rel -1".into(), 2));
+        assert_eq!(disassemble_at(&ram, 7).unwrap(), ("HALT".into(), 1));
+        assert!(matches!(disassemble_at(&ram, 4), Err(Error::Opcode(33))));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let rom = Rom(vec![1002i64, 4, 3, 4, 33, 204, -1, 99]);
+        let expected = "0: MUL [4], 3, [4]\n4: DATA 33\n5: OUT rel -1\n7: HALT\n";
+        assert_eq!(disassemble(&rom), expected);
+    }
+
+    #[test]
+    fn test_assemble() {
+        let src = "ADD 2, 3, [7]\nOUT [7]\n\nhalt\nDATA 0\n";
+        let rom = assemble(src).unwrap();
+        assert_eq!(&rom[..], &[1101, 2, 3, 7, 4, 7, 99, 0]);
+        let mut computer = ComputerST::new(&rom);
+        computer.run().unwrap();
+        assert_eq!(computer.collect_outputs(), vec![5]);
+
+        let rom = assemble("0: ARB rel -3\n2: JT [1], 7\n5: DATA -1").unwrap();
+        assert_eq!(&rom[..], &[209, -3, 1005, 1, 7, -1]);
+
+        for &day in &[2, 5, 9] {
+            let Some(reader) = utils::tests::data_file(day) else {
+                continue;
+            };
+            let rom = Rom::<i64>::from_reader(reader).unwrap();
+            assert_eq!(&assemble(&disassemble(&rom)).unwrap()[..], &rom[..]);
+        }
+
+        let e = assemble("HALT\nJMP 3").unwrap_err();
+        assert!(e.to_string().starts_with("Failed to assemble line 2: "));
+        assert!(assemble("ADD 1, 2").is_err());
+        assert!(assemble("x: HALT").is_err());
+    }
+
+    #[test]
+    fn test_repl() {
+        // Echoes inputs until it reads a zero.
+        let rom = Rom(vec![3, 11, 1006, 11, 10, 4, 11, 1105, 1, 0, 99, 0]);
+        let mut buf = Vec::new();
+        repl(&rom, "5, -3\nA\n0\n".as_bytes(), &mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "5\n-3\n65\n10\n");
+
+        let e = repl(&rom, "1\n".as_bytes(), io::sink()).unwrap_err();
+        assert!(e.to_string().contains("no more to read"));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        // Prints 0, 1, 2, ... forever.
+        let rom = Rom(vec![4, 9, 1001, 9, 1, 9, 1105, 1, 0, 0]);
+        let mut computer = ComputerST::new(&rom);
+        let next = |computer: &mut ComputerST| {
+            assert_eq!(computer.step().unwrap(), State::HasOutput);
+            computer.output.pop_front().unwrap()
+        };
+
+        assert_eq!(next(&mut computer), 0);
+        computer.checkpoint("one");
+        assert_eq!(next(&mut computer), 1);
+        assert_eq!(next(&mut computer), 2);
+        computer.input.push_back(42);
+        computer.checkpoint("three");
+        computer.input.clear();
+        assert_eq!(next(&mut computer), 3);
+
+        computer.rollback("one").unwrap();
+        assert!(computer.input.is_empty());
+        assert_eq!(next(&mut computer), 1);
+        computer.rollback("three").unwrap();
+        assert_eq!(computer.input, vec![42]);
+        assert_eq!(next(&mut computer), 3);
+        computer.rollback("one").unwrap();
+        assert_eq!(next(&mut computer), 1);
+
+        assert!(computer.rollback("two").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        // Doubles its input.
+        let double = [3i64, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+        // Outputs the sum of its two inputs.
+        let add = [3i64, 11, 3, 12, 1, 11, 12, 11, 4, 11, 99, 0, 0];
+
+        let mut computer = ComputerST::new(double);
+        computer.input.push_back(21);
+        computer.run().unwrap();
+        assert_eq!(computer.output.pop_front(), Some(42));
+
+        // Leave stale input and output behind, which `load` should discard.
+        computer.input.push_back(7);
+        computer.output.push_back(7);
+        computer.load(&add);
+        assert_eq!(computer.program_len(), add.len());
+        assert_eq!(computer.memory(), &add[..]);
+        computer.input.extend(vec![2, 3]);
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![5]);
+
+        computer.load(&double);
+        assert_eq!(computer.memory(), &double[..]);
+        computer.input.push_back(5);
+        computer.run().unwrap();
+        assert_eq!(computer.output, vec![10]);
+    }
+
+    #[test]
+    fn test_rom_from_lines() {
+        let input = "1,0,0,0,99\n\n104,7,99\n";
+        let roms = Rom::<i64>::from_lines(input.as_bytes()).unwrap();
+        assert_eq!(roms.len(), 2);
+        assert_eq!(&roms[0][..], &[1, 0, 0, 0, 99]);
+        assert_eq!(&roms[1][..], &[104, 7, 99]);
+
+        let e = Rom::<i64>::from_lines("99\n\n1,x,3\n".as_bytes()).unwrap_err();
+        assert!(e
+            .to_string()
+            .starts_with("Failed to parse the program on line 3: "));
+    }
+
+    #[test]
+    fn test_rom_comments() {
+        let input = "\
+            # Adds its input to 1 and outputs the sum.
+            3,0,        # read into address 0
+            1001, 0, 1, 0  # add 1
+            4,0         # output
+            99
+        ";
+        let rom = Rom::<i64>::from_reader(input.as_bytes()).unwrap();
+        let expected = Rom::<i64>::from_reader("3,0,1001,0,1,0,4,0,99\n".as_bytes()).unwrap();
+        assert_eq!(&rom[..], &expected[..]);
+
+        let mut computer = ComputerST::with_input(&rom, [41]);
+        computer.run().unwrap();
+        assert_eq!(computer.collect_outputs(), vec![42]);
+
+        let roms = Rom::<i64>::from_lines("# two programs\n99\n104,1,99 # echo\n".as_bytes());
+        assert_eq!(roms.unwrap().len(), 2);
+
+        assert!(Rom::<i64>::from_reader("1,,2".as_bytes()).is_err());
+        assert!(Rom::<i64>::from_reader("1,\n,2".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_rom_empty() {
+        for input in &["", "   \n", "# nothing but a comment\n"] {
+            let e = Rom::<i64>::from_reader(input.as_bytes()).unwrap_err();
+            assert_eq!(e.to_string(), "Cannot parse an empty program.");
+        }
+
+        let rom = Rom::<i64>::from_reader("99".as_bytes()).unwrap();
+        assert_eq!(rom.len(), 1);
+        assert!(!rom.is_empty());
+    }
+
+    #[test]
+    fn test_rom_patch() {
+        let mut rom = Rom::<i64>::from_reader("1,0,0,0,99".as_bytes()).unwrap();
+        rom.patch(1, 12).unwrap();
+        rom.patch(4, 98).unwrap();
+        assert_eq!(&rom[..], &[1, 12, 0, 0, 98]);
+
+        let e = rom.patch(5, 0).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Cannot patch address 5 of a program of length 5."
+        );
+        assert_eq!(rom.len(), 5);
+    }
+
+    #[test]
+    fn test_state() {
+        let mut computer = ComputerST::new([3i64, 7, 4, 7, 104, 5, 99, 0]);
+        assert_eq!(computer.state(), None);
+        assert_eq!(computer.step().unwrap(), State::NeedsInput);
+        assert_eq!(computer.state(), Some(State::NeedsInput));
+        assert_eq!(computer.state(), Some(State::NeedsInput));
+
+        computer.input.push_back(1);
+        assert_eq!(computer.step().unwrap(), State::HasOutput);
+        assert_eq!(computer.state(), Some(State::HasOutput));
+
+        computer.run().unwrap();
+        assert_eq!(computer.state(), Some(State::Done));
+
+        assert_eq!(computer.output, vec![1, 5]);
+
+        computer.load(&[99]);
+        assert_eq!(computer.state(), None);
+    }
+
+    #[test]
+    fn test_ring_queue() {
+        let mut queue = RingQueue::<3>::default();
+        assert!(queue.dequeue().is_err());
+        for val in 1..=3 {
+            queue.enqueue(val).unwrap();
+        }
+        assert!(queue.enqueue(4).is_err());
+
+        assert_eq!(queue.dequeue().unwrap(), 1);
+        queue.enqueue(4).unwrap();
+        for expected in 2..=4 {
+            assert_eq!(queue.dequeue().unwrap(), expected);
+        }
+        assert!(queue.dequeue().is_err());
+    }
+
+    #[test]
+    fn test_try_dequeue() {
+        let mut queue = VecDeque::<i64>::default();
+        assert_eq!(queue.try_dequeue(), None);
+        queue.enqueue(1).unwrap();
+        assert_eq!(queue.try_dequeue(), Some(1));
+
+        let mut queue = RingQueue::<1>::default();
+        assert_eq!(queue.try_dequeue(), None);
+        queue.enqueue(2).unwrap();
+        assert_eq!(queue.try_dequeue(), Some(2));
+
+        let mut channel = Channel::<i64>::default();
+        let start = std::time::Instant::now();
+        assert_eq!(channel.try_dequeue(), None);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        channel.enqueue(3).unwrap();
+        assert_eq!(channel.try_dequeue(), Some(3));
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut queue = VecDeque::<i64>::default();
+        assert_eq!((Queue::peek(&queue), Queue::len(&queue)), (None, 0));
+        queue.enqueue(1).unwrap();
+        queue.enqueue(2).unwrap();
+        assert_eq!((Queue::peek(&queue), Queue::len(&queue)), (Some(1), 2));
+        assert_eq!(Queue::peek(&queue), Some(1));
+        assert_eq!(queue.dequeue().unwrap(), 1);
+        assert_eq!((Queue::peek(&queue), Queue::len(&queue)), (Some(2), 1));
+
+        let mut queue = RingQueue::<2>::default();
+        assert_eq!((queue.peek(), queue.len()), (None, 0));
+        queue.enqueue(3).unwrap();
+        assert_eq!((queue.peek(), queue.len()), (Some(3), 1));
+        assert_eq!(queue.dequeue().unwrap(), 3);
+        assert!(queue.is_empty());
+
+        let mut channel = Channel::<i64>::default();
+        assert_eq!((channel.peek(), channel.len()), (None, 0));
+        channel.enqueue(4).unwrap();
+        channel.enqueue(5).unwrap();
+        assert_eq!((channel.peek(), channel.len()), (Some(4), 2));
+        assert_eq!((channel.peek(), channel.len()), (Some(4), 2));
+        // A clone shares the peeked value.
+        assert_eq!(channel.clone().dequeue().unwrap(), 4);
+        assert_eq!((channel.peek(), channel.len()), (Some(5), 1));
+        assert_eq!(channel.try_dequeue(), Some(5));
+        assert!(channel.is_empty());
+    }
+
+    #[test]
+    fn test_input_from_ascii_art() {
+        let mut computer = ComputerST::new([99]);
+        computer.input_from_ascii_art("#.\n.#").unwrap();
+        let input = computer.input_mut().drain(..).collect::<Vec<_>>();
+        assert_eq!(input, vec![35, 46, 10, 46, 35, 10]);
+    }
+
+    #[test]
+    fn test_apply_noun_verb() {
+        let rom = Rom::from_reader(io::BufReader::new("1,0,0,0,99".as_bytes())).unwrap();
+        let computer = apply_noun_verb(&rom, 12, 2).unwrap();
+        assert_eq!(computer.memory(), &[1, 12, 2, 0, 99]);
+
+        let rom = Rom::from_reader(io::BufReader::new("99,0".as_bytes())).unwrap();
+        assert!(apply_noun_verb(&rom, 12, 2).is_err());
+    }
+
+    #[test]
+    fn test_set_trace() {
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let mut computer = ComputerST::new([1, 0, 0, 0, 99]);
+        let recorded = trace.clone();
+        computer.set_trace(Box::new(move |pc, instruction| {
+            recorded.lock().unwrap().push((pc, instruction.to_string()));
+        }));
+        computer.run().unwrap();
+        let expected = vec![
+            (0, "ADD [0], [0], [0]".to_string()),
+            (4, "HALT".to_string()),
+        ];
+        assert_eq!(*trace.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_hooks() {
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let recorded = trace.clone();
+        let hooks = Hooks::with_trace(Box::new(move |pc, _: &str| {
+            recorded.lock().unwrap().push(pc)
+        }));
+
+        let mut computer = ComputerST::new([1, 0, 0, 0, 99]);
+        hooks.apply(&mut computer);
+        computer.run().unwrap();
+        assert_eq!(*trace.lock().unwrap(), [0, 4]);
+
+        // Every computer the hooks are applied to shares the one callback.
+        let mut computer =
+            ComputerMT::new([1, 0, 0, 0, 99], Channel::default(), Channel::default());
+        hooks.apply(&mut computer);
+        std::thread::spawn(move || computer.run().unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(*trace.lock().unwrap(), [0, 4, 0, 4]);
+
+        // The default hooks leave computers alone.
+        let mut computer = ComputerST::new([1, 0, 0, 0, 99]);
+        Hooks::default().apply(&mut computer);
+        computer.run().unwrap();
+        assert_eq!(trace.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let program = [1101, 1, 1, 10_000_000, 99];
+        let mut computer = ComputerST::new(program).with_memory_limit(1024);
+        assert!(computer.run().is_err());
+        assert_eq!(computer.ram_len(), 5);
+
+        let mut computer = ComputerST::new(program);
+        computer.run().unwrap();
+        assert_eq!(computer.read(10_000_000), 2);
+    }
+
+    #[test]
+    fn test_is_waiting_for_input() {
+        let reader = io::BufReader::new("3,0,99".as_bytes());
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        assert!(!computer.is_waiting_for_input());
+        assert_eq!(computer.step().unwrap(), State::NeedsInput);
+        assert!(computer.is_waiting_for_input());
+
+        computer.input_mut().enqueue(5).unwrap();
+        assert_eq!(computer.step().unwrap(), State::Done);
+        assert!(!computer.is_waiting_for_input());
+    }
+
+    #[test]
+    fn test_i128_cells() {
+        let big = i64::MAX as i128 + 1;
+        let program = format!("1102,{},{},7,4,7,99,0", big, big);
+        let rom = Rom::<i128>::from_reader(io::BufReader::new(program.as_bytes())).unwrap();
+        let mut computer = Computer::<VecDeque<i128>, i128>::new(&rom);
+        computer.run().unwrap();
+        assert_eq!(computer.output_mut().dequeue().unwrap(), big * big);
+    }
+
+    #[test]
+    fn test_run_to_output() {
+        let mut computer = ComputerST::new([3, 9, 4, 9, 1105, 1, 0, 99, 0, 0]);
+        for input in 1..=2 {
+            computer.input_mut().enqueue(input).unwrap();
+            assert_eq!(computer.run_to_output().unwrap(), Some(input));
+        }
+        assert!(matches!(computer.run_to_output(), Err(Error::NeedsInput)));
+
+        let mut computer = ComputerST::new([99]);
+        assert_eq!(computer.run_to_output().unwrap(), None);
+    }
+
+    #[test]
+    fn test_execute_until_input() {
+        let mut computer = ComputerST::new([104, 1, 104, 2, 3, 9, 4, 9, 99, 0]);
+        assert_eq!(computer.execute_until_input().unwrap(), (vec![1, 2], false));
+        computer.input_mut().enqueue(3).unwrap();
+        assert_eq!(computer.execute_until_input().unwrap(), (vec![3], true));
+    }
+
+    #[test]
+    fn test_outputs() {
+        let program = [
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut computer = ComputerST::new(program);
+        let outputs = computer.outputs().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(outputs, program.to_vec());
+
+        let mut computer = ComputerST::new([3, 0, 99]);
+        let mut outputs = computer.outputs();
+        assert!(matches!(outputs.next(), Some(Err(Error::NeedsInput))));
+        assert!(outputs.next().is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut computer = ComputerST::new([98]);
+        assert!(matches!(computer.run(), Err(Error::Opcode(98))));
+
+        let mut computer = ComputerST::new([301, 0, 0, 0, 99]);
+        assert!(matches!(computer.run(), Err(Error::AddressingMode(3))));
+
+        let mut computer = ComputerST::new([4, -1, 99]);
+        assert!(matches!(computer.run(), Err(Error::NegativePointer(-1))));
+
+        let mut computer = ComputerST::new([3, 0, 99]);
+        assert!(matches!(computer.run(), Err(Error::NeedsInput)));
+    }
+
+    #[test]
+    fn test_ram_len() {
+        let mut computer = ComputerST::new([1101, 1, 2, 100, 99]);
+        assert_eq!(computer.program_len(), 5);
+        assert_eq!(computer.ram_len(), 5);
+        computer.run().unwrap();
+        assert_eq!(computer.program_len(), 5);
+        assert_eq!(computer.ram_len(), 101);
+        assert_eq!(computer.read(100), 3);
+    }
+
+    #[test]
+    fn test_channel_timeout() {
+        let mut channel = Channel::<i64>::with_timeout(Duration::from_millis(0));
+        assert!(matches!(
+            channel.dequeue(),
+            Err(Error::Timeout { duration }) if duration == Duration::from_millis(0)
+        ));
+    }
+
+    #[test]
+    fn test_core_dump() {
+        // Adds 2 and 3 into address 0, then hits an unrecognized opcode.
+        let rom = Rom::from_reader("1101,2,3,0,42,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        assert!(matches!(computer.run(), Err(Error::Opcode(42))));
+
+        let mut computer = ComputerST::new(&rom);
+        computer.input_mut().enqueue(7).unwrap();
+        computer.set_dump_on_error(true);
+        let e = computer.run().unwrap_err();
+        match &e {
+            Error::CoreDump { dump, source } => {
+                assert_eq!(*dump, computer.core_dump());
+                assert!(matches!(**source, Error::Opcode(42)));
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+        assert!(std::error::Error::source(&e).is_some());
+        assert_eq!(
+            e.to_string(),
+            "Unrecognized opcode 42\n\
+             pc: 4\n\
+             rb: 0\n\
+             last state: not stepped yet\n\
+             inputs queued: 1\n\
+             outputs queued: 0\n\
+             memory:\n\
+             000000: 5 2 3 0 42 99"
+        );
+        assert_eq!(
+            e.to_string(),
+            format!("Unrecognized opcode 42\n{}", computer.core_dump())
+        );
+    }
+
+    #[test]
+    fn test_set_output_sink() {
+        // Outputs 1 through 5.
+        let rom = Rom::from_reader("104,1,104,2,104,3,104,4,104,5,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        computer.run().unwrap();
+        assert_eq!(computer.output_len(), 5);
+
+        let sum = Arc::new(Mutex::new(0));
+        let mut computer = ComputerST::new(&rom);
+        let sink_sum = Arc::clone(&sum);
+        computer.set_output_sink(Box::new(move |val| *sink_sum.lock().unwrap() += val));
+        assert_eq!(computer.step().unwrap(), State::HasOutput);
+        computer.run().unwrap();
+        assert_eq!(*sum.lock().unwrap(), 15);
+        assert_eq!(computer.output_len(), 0);
+
+        let mut computer = ComputerST::new(&rom);
+        computer.set_output_sink(Box::new(|_| ()));
+        let e = computer.run_to_output().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Cannot run to an output when outputs go to an output sink."
+        );
+        assert!(computer.outputs().next().unwrap().is_err());
+        assert_eq!(computer.execute_until_input().unwrap(), (Vec::new(), true));
+    }
+
+    #[test]
+    fn test_profile() {
+        let rom = Rom::from_reader("1,9,10,3,2,3,11,0,99,30,40,50".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        computer.run().unwrap();
+        assert!(computer.profile().is_none());
+
+        let mut computer = ComputerST::new(&rom);
+        computer.enable_profiling();
+        computer.run().unwrap();
+        let profile = computer.profile().unwrap();
+        assert_eq!(profile[&1], 1);
+        assert_eq!(profile[&2], 1);
+        assert_eq!(profile[&99], 1);
+        assert_eq!(profile.len(), 3);
+
+        let computer = computer.clone_fresh();
+        assert!(computer.profile().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_with_input() {
+        // Day 5's diagnostic program with system ID 1, as in part 1.
+        if let Some(reader) = utils::tests::data_file(5) {
+            let rom = Rom::from_reader(reader).unwrap();
+            let mut computer = ComputerST::with_input(&rom, [1]);
+            computer.run().unwrap();
+            assert_eq!(computer.collect_outputs().pop(), Some(2845163));
+        }
+
+        let mut computer = ComputerST::with_input(Vec::new(), 1..4);
+        assert_eq!(
+            computer.input_mut().drain(..).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_feed() {
+        let mut computer = ComputerST::new(Vec::new());
+        computer.feed(&[1, 2, 3]).unwrap();
+        let input = computer.input_mut();
+        assert_eq!(input.dequeue().unwrap(), 1);
+        assert_eq!(input.dequeue().unwrap(), 2);
+        assert_eq!(input.dequeue().unwrap(), 3);
+        assert!(input.is_empty());
+
+        // Echoes its three inputs.
+        let rom = Rom::from_reader("3,0,4,0,3,0,4,0,3,0,4,0,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        computer.feed(&[1, 2, 3]).unwrap();
+        computer.run().unwrap();
+        assert_eq!(computer.collect_outputs(), vec![1, 2, 3]);
+        assert!(computer.collect_outputs().is_empty());
+    }
+
+    #[test]
+    fn test_clone_fresh() {
+        // Outputs 1, 2, and 3, then waits for input.
+        let rom = Rom::from_reader("104,1,104,2,104,3,3,0,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        assert_eq!(computer.run_to_output().unwrap(), Some(1));
+        assert_eq!(computer.run_to_output().unwrap(), Some(2));
+        computer.input_mut().push_back(7);
+
+        let mut fresh = computer.clone_fresh();
+        assert!(fresh.input_mut().is_empty());
+        assert_eq!(fresh.run_to_output().unwrap(), Some(1));
+        assert_eq!(computer.run_to_output().unwrap(), Some(3));
+
+        let (input, output) = (Channel::default(), Channel::default());
+        let computer = ComputerMT::new(&rom, input.clone(), output);
+        input.clone().enqueue(7).unwrap();
+        let mut fresh = computer.clone_fresh();
+        assert!(fresh.input_mut().is_empty());
+        assert_eq!(input.len(), 1);
+    }
+
+    #[test]
+    fn test_set_input_fn() {
+        // Echoes three inputs.
+        let rom = Rom::from_reader("3,0,4,0,3,0,4,0,3,0,4,0,99".as_bytes()).unwrap();
+        let mut computer = ComputerST::new(&rom);
+        let mut counter = 0;
+        computer.set_input_fn(Box::new(move || {
+            counter += 1;
+            Ok(counter)
+        }));
+        computer.run().unwrap();
+        assert_eq!(computer.collect_outputs(), vec![1, 2, 3]);
+
+        let mut computer = ComputerST::new(&rom);
+        computer.set_input_fn(Box::new(|| Err(error!("no more input"))));
+        assert_eq!(computer.run().unwrap_err().to_string(), "no more input");
+    }
+
+    #[test]
+    fn test_set_label() {
+        let rom = Rom::from_reader("3,0,99".as_bytes()).unwrap();
+        let input = Channel::with_timeout(Duration::from_millis(10));
+        let mut computer = ComputerMT::new(&rom, input.clone(), Channel::default());
+        computer.set_label("amplifier 3");
+
+        // The label doesn't change how the computer runs, only the error from `run`.
+        assert_eq!(computer.step().unwrap(), State::NeedsInput);
+        let e = computer.run().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "amplifier 3: Attempted to pop value off channel, but timed out after 10ms."
+        );
+        match e {
+            Error::Labeled { label, source } => {
+                assert_eq!(label, "amplifier 3");
+                assert!(matches!(*source, Error::Timeout { .. }));
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let mut computer = ComputerMT::new(&rom, input, Channel::default());
+        assert!(matches!(computer.run(), Err(Error::Timeout { .. })));
+    }
+
+    #[test]
+    fn test_output_drain_into() {
+        let reader = io::BufReader::new("3,0,4,0,4,0,99".as_bytes());
+        let rom = Rom::from_reader(reader).unwrap();
+
+        let mut buf = Vec::with_capacity(8);
+        let ptr = buf.as_ptr();
+        for input in 1..=2 {
+            let mut computer = ComputerST::new(&rom);
+            computer.input_mut().enqueue(input).unwrap();
+            computer.run().unwrap();
+            buf.clear();
+            computer.output_drain_into(&mut buf);
+            assert_eq!(buf, vec![input, input]);
+            assert!(computer.output_mut().is_empty());
         }
+        assert_eq!(buf.as_ptr(), ptr);
     }
 }
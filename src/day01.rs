@@ -1,39 +1,35 @@
 use std::io;
 
 use crate::error::Error;
+use crate::utils;
 
-pub fn run<R>(mut input: R) -> Result<(String, String), Error>
+pub fn run<R>(input: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
-    let mut buffer = String::new();
-    let mut total1 = 0;
-    let mut total2 = 0;
+    let mut total1 = 0u64;
+    let mut total2 = 0u64;
 
-    loop {
-        if input.read_line(&mut buffer)? == 0 {
-            break;
-        }
-
-        let n = buffer.trim().parse::<usize>()?;
-
-        total1 += part_one(n);
-        total2 += part_two(n);
+    utils::for_each_line(input, |lineno, line| {
+        let n = line.parse::<u64>()?;
 
-        buffer.clear();
-    }
+        let overflow = || error!("Total fuel overflows a u64 at line {}.", lineno);
+        total1 = total1.checked_add(part_one(n)).ok_or_else(overflow)?;
+        total2 = total2.checked_add(part_two(n)).ok_or_else(overflow)?;
+        Ok(())
+    })?;
 
     Ok((total1.to_string(), total2.to_string()))
 }
 
-fn part_one(n: usize) -> usize {
+fn part_one(n: u64) -> u64 {
     match (n / 3).checked_sub(2) {
         Some(m) => m,
         None => 0,
     }
 }
 
-fn part_two(mut n: usize) -> usize {
+fn part_two(mut n: u64) -> u64 {
     let mut total = 0;
     loop {
         let m = match (n / 3).checked_sub(2) {
@@ -70,4 +66,15 @@ mod tests {
 
         utils::tests::test_full_problem(1, run, "3271095", "4903759");
     }
+
+    #[test]
+    fn test_01_overflow() {
+        // Part two's fuel for a single module is a bit less than half its mass, so the
+        // third of these modules pushes the total past u64::MAX. The blank line still
+        // counts towards the reported line number.
+        let input = format!("{0}\n{0}\n\n{0}\n", u64::MAX);
+        let reader = io::BufReader::new(input.as_bytes());
+        let e = run(reader).unwrap_err();
+        assert_eq!(e.to_string(), "Total fuel overflows a u64 at line 4.");
+    }
 }
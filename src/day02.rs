@@ -1,37 +1,86 @@
 use std::io;
 
-use crate::computer::{ComputerST, Rom};
+use crate::computer::{apply_noun_verb, Hooks, Rom};
 use crate::error::Error;
 
+const TARGET: i64 = 19_690_720;
+
 pub fn run<R>(input: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(input, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(input: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(input)?;
 
-    let mut computer = ComputerST::new(&rom);
-    computer.write(1, 12);
-    computer.write(2, 2);
+    let answer1 = output(&rom, hooks, 12, 2)?;
+
+    let (noun, verb) = match solve_linear(&rom, hooks, TARGET) {
+        Some(pair) => pair,
+        None => search(&rom, hooks, TARGET)?.ok_or_else(|| {
+            error!(
+                "Invalid input. Unable to find noun/verb combination that outputs {}.",
+                TARGET
+            )
+        })?,
+    };
+    let answer2 = 100 * noun + verb;
+
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Runs the program with the given noun and verb and returns what it leaves at
+/// address 0.
+fn output(rom: &Rom, hooks: &Hooks, noun: i64, verb: i64) -> Result<i64, Error> {
+    let mut computer = apply_noun_verb(rom, noun, verb)?;
+    hooks.apply(&mut computer);
     computer.run()?;
-    let answer1 = computer.read(0);
+    Ok(computer.memory()[0])
+}
+
+/// Finds a noun and verb for which the program outputs `target` with three runs
+/// instead of up to 10,000, assuming the output is linear in the noun and verb (as
+/// it is for real inputs, which only ever add and multiply them by constants). The
+/// answer is checked with a fourth run, so this returns `None` rather than a wrong
+/// answer if the assumption doesn't hold.
+fn solve_linear(rom: &Rom, hooks: &Hooks, target: i64) -> Option<(i64, i64)> {
+    let base = output(rom, hooks, 0, 0).ok()?;
+    let dn = output(rom, hooks, 1, 0).ok()? - base;
+    let dv = output(rom, hooks, 0, 1).ok()? - base;
 
-    let mut answer2 = Err(error!(
-        "Invalid input. Unable to find noun/verb combination that outputs 19690720."
-    ));
-    'outer: for noun in 0..=99 {
+    let (noun, verb) = (0..=99).find_map(|noun| {
+        let rest = target - base - dn * noun;
+        match dv {
+            0 if rest == 0 => Some((noun, 0)),
+            0 => None,
+            _ if rest % dv == 0 && (0..=99).contains(&(rest / dv)) => Some((noun, rest / dv)),
+            _ => None,
+        }
+    })?;
+
+    if output(rom, hooks, noun, verb).ok()? == target {
+        Some((noun, verb))
+    } else {
+        None
+    }
+}
+
+/// Tries every noun and verb from 0 through 99 until the program outputs `target`.
+fn search(rom: &Rom, hooks: &Hooks, target: i64) -> Result<Option<(i64, i64)>, Error> {
+    for noun in 0..=99 {
         for verb in 0..=99 {
-            let mut computer = ComputerST::new(&rom);
-            computer.write(1, noun);
-            computer.write(2, verb);
-            computer.run()?;
-            if computer.read(0) == 19_690_720 {
-                answer2 = Ok(100 * noun + verb);
-                break 'outer;
+            if output(rom, hooks, noun, verb)? == target {
+                return Ok(Some((noun, verb)));
             }
         }
     }
-
-    Ok((answer1.to_string(), answer2?.to_string()))
+    Ok(None)
 }
 
 #[cfg(test)]
@@ -44,4 +93,19 @@ mod tests {
     fn test_02() {
         utils::tests::test_full_problem(2, run, "3267740", "7870");
     }
+
+    #[test]
+    fn test_02_solve_linear() {
+        let hooks = Hooks::default();
+        if let Some(reader) = utils::tests::data_file(2) {
+            let rom = Rom::from_reader(reader).unwrap();
+            assert_eq!(solve_linear(&rom, &hooks, TARGET), Some((78, 70)));
+            assert_eq!(search(&rom, &hooks, TARGET).unwrap(), Some((78, 70)));
+        }
+
+        // Outputs noun * verb, which isn't linear in them.
+        let rom = Rom::from_reader("1102,1,2,0,99".as_bytes()).unwrap();
+        assert_eq!(solve_linear(&rom, &hooks, 42), None);
+        assert_eq!(search(&rom, &hooks, 42).unwrap(), Some((1, 42)));
+    }
 }
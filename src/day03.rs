@@ -4,10 +4,58 @@ use std::io;
 use std::ops::{Add, Deref, DerefMut};
 
 use crate::error::Error;
+use crate::utils::direction;
 
 const ORIGIN: Point = Point { x: 0, y: 0 };
 
-pub fn run<R>(input: R) -> Result<(String, String), Error>
+/// Roughly how many times longer it takes to record one point of a wire in
+/// `day03_v2`'s map than to check one pair of segments for intersections here.
+const POINT_COST: u64 = 10;
+
+/// Solves day 3 with whichever of the two implementations should be faster for
+/// `input`. `run_segments` compares every segment of one wire with every segment
+/// of the other, so its cost grows with the product of the wires' segment counts;
+/// `day03_v2` records every point each wire passes through, so its cost grows with
+/// the wires' total length. Puzzle inputs have long segments, so `run_segments` is
+/// the usual choice, but wires made of many short segments go to `day03_v2`, with
+/// its map sized for the points it will record.
+pub fn run<R>(mut input: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    let mut buf = String::new();
+    input.read_to_string(&mut buf)?;
+    match walking_capacity(&buf) {
+        Some(capacity) => crate::day03_v2::run_with_capacity(buf.as_bytes(), capacity),
+        None => run_segments(buf.as_bytes()),
+    }
+}
+
+/// If walking every point of the wires (`day03_v2`) should beat intersecting their
+/// segments pairwise, returns the wires' total length, which bounds the number of
+/// points `day03_v2` will record. Malformed instructions are counted as zero-length
+/// segments here and reported by whichever implementation runs.
+fn walking_capacity(input: &str) -> Option<usize> {
+    let (mut length, mut pairs) = (0u64, 1u64);
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let mut nsegments = 0;
+        for s in line.trim().split(',') {
+            nsegments += 1;
+            let dist = atoi::atoi::<u64>(s.trim().get(1..).unwrap_or("").as_bytes());
+            length = length.saturating_add(dist.unwrap_or(0));
+        }
+        pairs = pairs.saturating_mul(nsegments);
+    }
+    if length.saturating_mul(POINT_COST) < pairs {
+        Some(length as usize)
+    } else {
+        None
+    }
+}
+
+/// Solves day 3 by intersecting each segment of one wire with each segment of the
+/// other.
+pub fn run_segments<R>(input: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
@@ -16,9 +64,7 @@ where
     let mut intersections = Vec::new();
     for segment0 in paths[0].iter() {
         for segment1 in paths[1].iter() {
-            if let Some(intersection) = segment0.intersection(segment1) {
-                intersections.push(intersection);
-            }
+            intersections.extend(segment0.intersections(segment1));
         }
     }
 
@@ -63,6 +109,11 @@ where
             break;
         }
 
+        if buffer.trim().is_empty() {
+            buffer.clear();
+            continue;
+        }
+
         if id > 1 {
             bail!("Invalid input. Input must be comprise of exactly 2 paths.")
         }
@@ -73,8 +124,10 @@ where
         for s in buffer.trim().split(',').map(|s| s.trim()) {
             let instruction = {
                 let bytes = s.as_bytes();
-                let c = bytes[0] as char;
-                let dir = Direction::try_from(c)?;
+                let dir = match bytes.first() {
+                    Some(&b) => Direction::try_from(b as char)?,
+                    None => bail!("Unable to parse an empty string into an instruction."),
+                };
                 let dist = atoi::atoi::<u64>(&bytes[1..])
                     .ok_or_else(|| error!("Unable to parse {} into an instruction.", s))?;
                 Instruction { dir, dist }
@@ -116,6 +169,17 @@ impl TryFrom<char> for Direction {
     }
 }
 
+impl From<Direction> for direction::Direction {
+    fn from(d: Direction) -> Self {
+        match d {
+            Direction::U => Self::North,
+            Direction::D => Self::South,
+            Direction::L => Self::West,
+            Direction::R => Self::East,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 struct Instruction {
     dir: Direction,
@@ -127,23 +191,10 @@ impl Add<Instruction> for Point {
 
     #[allow(clippy::suspicious_arithmetic_impl)]
     fn add(self, rhs: Instruction) -> Self::Output {
-        match rhs.dir {
-            Direction::U => Point {
-                x: self.x,
-                y: self.y + rhs.dist as i64,
-            },
-            Direction::D => Point {
-                x: self.x,
-                y: self.y - rhs.dist as i64,
-            },
-            Direction::L => Point {
-                x: self.x - rhs.dist as i64,
-                y: self.y,
-            },
-            Direction::R => Point {
-                x: self.x + rhs.dist as i64,
-                y: self.y,
-            },
+        let delta = direction::Direction::from(rhs.dir).delta() * rhs.dist as i64;
+        Point {
+            x: self.x + delta.x(),
+            y: self.y + delta.y(),
         }
     }
 }
@@ -179,45 +230,80 @@ impl Segment {
         self.steps + new_steps
     }
 
-    fn intersection(&self, other: &Segment) -> Option<Intersection> {
-        match self.kind() {
-            SegmentKind::Horizontal { x, y } => {
-                let (xh, yh) = (x, y);
-                match other.kind() {
-                    SegmentKind::Vertical { x, y } => {
-                        let (xv, yv) = (x, y);
-                        if (xh.0..=xh.1).contains(&xv) && (yv.0..=yv.1).contains(&yh) {
-                            let point = Point { x: xv, y: yh };
-                            if point != ORIGIN {
-                                let steps = self.steps_to(point) + other.steps_to(point);
-                                return Some(Intersection { point, steps });
-                            }
-                        }
-                        None
+    /// Points (other than the origin) where this segment meets `other`. Collinear
+    /// segments can overlap along a whole run of points, in which case only the
+    /// points that could be closest to the origin or have the fewest combined
+    /// steps are returned.
+    fn intersections(&self, other: &Segment) -> Vec<Intersection> {
+        match (self.kind(), other.kind()) {
+            (SegmentKind::Horizontal { x: xh, y: yh }, SegmentKind::Vertical { x: xv, y: yv })
+            | (SegmentKind::Vertical { x: xv, y: yv }, SegmentKind::Horizontal { x: xh, y: yh }) => {
+                let mut intersections = Vec::new();
+                if (xh.0..=xh.1).contains(&xv) && (yv.0..=yv.1).contains(&yh) {
+                    let point = Point { x: xv, y: yh };
+                    if point != ORIGIN {
+                        let steps = self.steps_to(point) + other.steps_to(point);
+                        intersections.push(Intersection { point, steps });
                     }
-                    _ => None,
                 }
+                intersections
             }
-            SegmentKind::Vertical { x, y } => {
-                let (xv, yv) = (x, y);
-                match other.kind() {
-                    SegmentKind::Horizontal { x, y } => {
-                        let (xh, yh) = (x, y);
-                        if (xh.0..=xh.1).contains(&xv) && (yv.0..=yv.1).contains(&yh) {
-                            let point = Point { x: xv, y: yh };
-                            if point != ORIGIN {
-                                let steps = self.steps_to(point) + other.steps_to(point);
-                                return Some(Intersection { point, steps });
-                            }
-                        }
-                        None
-                    }
-                    _ => None,
+            (
+                SegmentKind::Horizontal { x: xa, y: ya },
+                SegmentKind::Horizontal { x: xb, y: yb },
+            ) => {
+                if ya != yb {
+                    return Vec::new();
+                }
+                self.collinear_intersections(other, xa, xb, |x| Point { x, y: ya }, |p| p.x)
+            }
+            (SegmentKind::Vertical { x: xa, y: ya }, SegmentKind::Vertical { x: xb, y: yb }) => {
+                if xa != xb {
+                    return Vec::new();
                 }
+                self.collinear_intersections(other, ya, yb, |y| Point { x: xa, y }, |p| p.y)
             }
         }
     }
 
+    /// Distance to the origin and combined steps are both piecewise linear along an
+    /// overlapping run, so each is minimized at an end of the run or next to one of
+    /// the points where its slope changes (the origin's coordinate and the two
+    /// segments' starting coordinates).
+    fn collinear_intersections<F, G>(
+        &self,
+        other: &Segment,
+        a: (i64, i64),
+        b: (i64, i64),
+        to_point: F,
+        coord: G,
+    ) -> Vec<Intersection>
+    where
+        F: Fn(i64) -> Point,
+        G: Fn(Point) -> i64,
+    {
+        let (lo, hi) = (cmp::max(a.0, b.0), cmp::min(a.1, b.1));
+        if lo > hi {
+            return Vec::new();
+        }
+        let mut candidates = vec![lo, hi];
+        for &c in &[0, coord(self.o), coord(other.o)] {
+            candidates.extend_from_slice(&[c - 1, c, c + 1]);
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+            .into_iter()
+            .filter(|c| (lo..=hi).contains(c))
+            .map(to_point)
+            .filter(|point| *point != ORIGIN)
+            .map(|point| Intersection {
+                point,
+                steps: self.steps_to(point) + other.steps_to(point),
+            })
+            .collect()
+    }
+
     fn kind(&self) -> SegmentKind {
         if self.o.x == self.d.x {
             SegmentKind::Vertical {
@@ -278,6 +364,10 @@ mod tests {
                 "135",
                 "410",
             ),
+            // Collinear overlapping runs
+            ("R10\nU1,R5,D1,L2", "3", "12"),
+            ("R10\nR5,U2", "1", "2"),
+            ("U10\nL1,U5,R1,U2", "5", "12"),
         ];
 
         for (input, expected1, expected2) in test_cases {
@@ -289,4 +379,46 @@ mod tests {
 
         utils::tests::test_full_problem(3, run, "4981", "164012");
     }
+
+    #[test]
+    fn test_03_dispatch() {
+        // A staircase of unit steps is short but has many segments.
+        let stairs = |first: char, second: char| {
+            (0..50)
+                .map(|_| format!("{}1,{}1", first, second))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let staircase = format!("{}\n{}", stairs('R', 'U'), stairs('U', 'R'));
+        assert!(walking_capacity(&staircase).is_some());
+
+        let mut test_cases = vec![
+            "R8,U5,L5,D3\nU7,R6,D4,L4".to_string(),
+            "R75,D30,R83,U83,L12,D49,R71,U7,L72\nU62,R66,U55,R34,D71,R55,D58,R83".to_string(),
+            "R98,U47,R26,D63,R33,U87,L62,D20,R33,U53,R51\nU98,R91,D20,R16,D67,R40,U7,R15,U6,R7"
+                .to_string(),
+        ];
+        for input in &test_cases {
+            assert!(walking_capacity(input).is_none());
+        }
+        test_cases.push(staircase);
+
+        for input in &test_cases {
+            let expected = run_segments(input.as_bytes()).unwrap();
+            assert_eq!(run(input.as_bytes()).unwrap(), expected);
+            assert_eq!(crate::day03_v2::run(input.as_bytes()).unwrap(), expected);
+
+            // Blank lines are skipped whichever implementation runs.
+            let blank_lines = format!("\n{}\n\n", input.replace('\n', "\n\n"));
+            assert_eq!(run(blank_lines.as_bytes()).unwrap(), expected);
+
+            // A third wire is an error whichever implementation runs.
+            let three_wires = format!("{}\nR1", input);
+            let e = run(three_wires.as_bytes()).unwrap_err();
+            assert_eq!(
+                e.to_string(),
+                "Invalid input. Input must be comprise of exactly 2 paths."
+            );
+        }
+    }
 }
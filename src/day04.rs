@@ -1,5 +1,7 @@
 use std::io;
 
+use crossbeam::thread;
+
 use crate::error::Error;
 
 pub fn run<R>(input: R) -> Result<(String, String), Error>
@@ -8,18 +10,50 @@ where
 {
     let (low, high) = read_input(input)?;
 
-    let (mut answer1, mut answer2) = (0, 0);
+    let nthreads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let (answer1, answer2) = count_valid_parallel(low, high, nthreads)?;
+
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Counts the passwords in `low..=high` that are valid under the rules of parts 1
+/// and 2, respectively.
+fn count_valid(low: usize, high: usize) -> Result<(usize, usize), Error> {
+    let (mut count1, mut count2) = (0, 0);
     for n in low..=high {
         let is_valid = is_valid(n)?;
         if is_valid.0 {
-            answer1 += 1;
+            count1 += 1;
         }
         if is_valid.1 {
-            answer2 += 1;
+            count2 += 1;
         }
     }
+    Ok((count1, count2))
+}
 
-    Ok((answer1.to_string(), answer2.to_string()))
+/// Like `count_valid`, but splits the range into `nthreads` chunks and counts each
+/// on its own thread.
+fn count_valid_parallel(low: usize, high: usize, nthreads: usize) -> Result<(usize, usize), Error> {
+    let chunk_size = (high - low) / nthreads.max(1) + 1;
+    thread::scope(|s| {
+        let handles = (low..=high)
+            .step_by(chunk_size)
+            .map(|start| {
+                let end = high.min(start + (chunk_size - 1));
+                s.spawn(move |_| count_valid(start, end))
+            })
+            .collect::<Vec<_>>();
+
+        let (mut count1, mut count2) = (0, 0);
+        for handle in handles {
+            let (chunk1, chunk2) = handle.join().unwrap()?;
+            count1 += chunk1;
+            count2 += chunk2;
+        }
+        Ok((count1, count2))
+    })
+    .unwrap()
 }
 
 fn is_valid(n: usize) -> Result<(bool, bool), Error> {
@@ -27,8 +61,8 @@ fn is_valid(n: usize) -> Result<(bool, bool), Error> {
     let mut min = 0;
     let mut previous_digits = PreviousDigits::None;
 
-    for (i, digit) in parse_digits(n)?.iter().enumerate() {
-        let digit = *digit;
+    let digits = parse_digits(n)?;
+    for (i, &digit) in digits.iter().enumerate() {
         if digit < min {
             return Ok((false, false));
         }
@@ -41,7 +75,7 @@ fn is_valid(n: usize) -> Result<(bool, bool), Error> {
             PreviousDigits::One(d) => {
                 if digit == d {
                     is_valid.0 = true;
-                    if i == 5 {
+                    if i == digits.len() - 1 {
                         is_valid.1 = true;
                     }
                     previous_digits = PreviousDigits::Two(digit);
@@ -73,37 +107,51 @@ fn read_input<R>(mut reader: R) -> Result<(usize, usize), Error>
 where
     R: io::BufRead,
 {
-    let parse = |s: &str| s.trim().parse::<usize>();
-    let error = || error!("Invalid input.");
-
     let mut s = String::new();
     reader.read_to_string(&mut s)?;
+    let s = s.trim();
+
+    let parts = s.split('-').collect::<Vec<_>>();
+    if parts.len() != 2 {
+        bail!(
+            "Invalid input. Expected a range of the form low-high, but got {:?}.",
+            s
+        )
+    }
 
-    let mut iter = s.split('-');
-    let low = iter.next().map(parse).ok_or_else(error)??;
-    let high = iter.next().map(parse).ok_or_else(error)??;
+    let parse = |side: &str, part: &str| {
+        part.trim().parse::<usize>().map_err(|_| {
+            error!(
+                "Invalid input. Cannot parse the {} end of the range, {:?}, as a number.",
+                side, part
+            )
+        })
+    };
+    let low = parse("low", parts[0])?;
+    let high = parse("high", parts[1])?;
 
-    if iter.next().is_some() {
-        bail!("Invalid input.")
+    if low > high {
+        bail!(
+            "Invalid input. The low end of the range, {}, is greater than the high end, {}.",
+            low,
+            high
+        )
     }
 
     Ok((low, high))
 }
 
-fn parse_digits(mut n: usize) -> Result<[u8; 6], Error> {
-    if n < 100_000 || n > 999_999 {
-        bail!("Input must be a 6 digit number.")
+/// Splits `n` into its decimal digits, most significant first.
+fn parse_digits(mut n: usize) -> Result<Vec<u8>, Error> {
+    if n == 0 {
+        bail!("Input must be a positive number.")
     }
-    let mut output = [0u8; 6];
-    let mut i = 5;
-    loop {
-        output[i] = (n % 10) as u8;
+    let mut output = Vec::new();
+    while n > 0 {
+        output.push((n % 10) as u8);
         n /= 10;
-        if i == 0 {
-            break;
-        }
-        i -= 1;
     }
+    output.reverse();
     Ok(output)
 }
 
@@ -139,4 +187,70 @@ mod tests {
 
         utils::tests::test_full_problem(4, run, "1929", "1306");
     }
+
+    #[test]
+    fn test_04_parallel() {
+        let serial = count_valid(111111, 112233).unwrap();
+        for nthreads in 1..=8 {
+            assert_eq!(
+                count_valid_parallel(111111, 112233, nthreads).unwrap(),
+                serial
+            );
+        }
+        assert_eq!(count_valid_parallel(111111, 111111, 4).unwrap(), (1, 0));
+    }
+
+    #[test]
+    fn test_04_read_input() {
+        let read = |s: &str| read_input(io::BufReader::new(s.as_bytes()));
+
+        assert_eq!(read("128392-643281\n").unwrap(), (128392, 643281));
+
+        let test_cases = &[
+            (
+                "123-456-789",
+                "Invalid input. Expected a range of the form low-high, but got \"123-456-789\".",
+            ),
+            (
+                "123456",
+                "Invalid input. Expected a range of the form low-high, but got \"123456\".",
+            ),
+            (
+                "abc-def",
+                "Invalid input. Cannot parse the low end of the range, \"abc\", as a number.",
+            ),
+            (
+                "123-def",
+                "Invalid input. Cannot parse the high end of the range, \"def\", as a number.",
+            ),
+            (
+                "456-123",
+                "Invalid input. The low end of the range, 456, is greater than the high end, 123.",
+            ),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(&read(input).unwrap_err().to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_04_other_widths() {
+        assert_eq!(parse_digits(1123).unwrap(), vec![1, 1, 2, 3]);
+        assert_eq!(parse_digits(7).unwrap(), vec![7]);
+        assert!(parse_digits(0).is_err());
+
+        let test_cases = &[
+            (1123, true, true),
+            (1233, true, true),
+            (1113, true, false),
+            (1110, false, false),
+            (1234, false, false),
+            (11, true, true),
+            (1111223, true, true),
+        ];
+
+        for (n, expected1, expected2) in test_cases {
+            assert_eq!(is_valid(*n).unwrap(), (*expected1, *expected2), "{}", n);
+        }
+    }
 }
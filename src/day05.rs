@@ -1,30 +1,38 @@
 use std::io;
 
-use crate::computer::{ComputerST, Queue, Rom};
+use crate::computer::{ComputerST, Hooks, Rom};
 use crate::error::Error;
 
 pub fn run<R>(input: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(input, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(input: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(input)?;
 
     // Part 1
-    let mut computer = ComputerST::new(&rom);
-    computer.input_mut().enqueue(1);
+    let mut computer = ComputerST::with_input(&rom, [1]);
+    hooks.apply(&mut computer);
     computer.run()?;
     let answer1 = computer
-        .output_mut()
-        .pop_back()
+        .collect_outputs()
+        .pop()
         .ok_or_else(|| error!("Nothing in output channel for part 1."))?;
 
     // Part 2
-    let mut computer = ComputerST::new(&rom);
-    computer.input_mut().enqueue(5);
+    let mut computer = ComputerST::with_input(&rom, [5]);
+    hooks.apply(&mut computer);
     computer.run()?;
     let answer2 = computer
-        .output_mut()
-        .pop_back()
+        .collect_outputs()
+        .pop()
         .ok_or_else(|| error!("Nothing in output channel for part 2."))?;
 
     Ok((answer1.to_string(), answer2.to_string()))
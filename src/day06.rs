@@ -1,13 +1,19 @@
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::HashMap;
+use std::env;
 use std::hash::Hash;
 use std::io;
 use std::iter::FromIterator;
 use std::ops::{Deref, DerefMut};
 
 use crate::error::Error;
+use crate::utils::{self, search};
 
 type Ids = HashMap<String, usize>;
 
+/// If this environment variable is set, `run` prints the chain of bodies between
+/// `YOU` and `SAN` to stderr, for checking part 2 by hand.
+const PATH_VAR: &str = "AOC2019_DAY06_PATH";
+
 pub fn run<R>(input: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
@@ -23,6 +29,12 @@ where
         .ok_or_else(|| error!("Could not find a path from us to Santa :( :("))?;
     let answer2 = shortest_distance - 2;
 
+    if env::var_os(PATH_VAR).is_some() {
+        if let Some(path) = undirected.shortest_path(&id_you, &id_san) {
+            eprintln!("{}", describe_path(&path, &ids));
+        }
+    }
+
     Ok((nconnections.to_string(), answer2.to_string()))
 }
 
@@ -67,83 +79,75 @@ impl<N> Graph<N>
 where
     N: Eq + Hash,
 {
-    fn shortest_distance(&self, a: &N, b: &N) -> Option<usize> {
-        let mut levels: HashMap<&N, usize> = HashMap::new();
-        let mut queue: VecDeque<&N> = VecDeque::new();
-        let mut visited: HashSet<&N> = HashSet::new();
-
-        levels.insert(a, 0);
-        queue.push_back(a);
-        visited.insert(a);
-
-        while let Some(ref node) = queue.pop_front() {
-            if let Some(children) = self.0.get(node) {
-                for child in children {
-                    if !visited.contains(child) {
-                        let level = levels.get(node).unwrap() + 1;
-                        if child == b {
-                            return Some(level);
-                        }
-
-                        levels.insert(child, level);
-
-                        visited.insert(child);
-                        queue.push_back(child);
-                    }
-                }
-            }
+    /// Builds a directed graph with an edge from `a` to `b` for every `(a, b)`.
+    #[allow(unused)]
+    fn from_edges<I>(edges: I) -> Self
+    where
+        I: IntoIterator<Item = (N, N)>,
+    {
+        let mut graph = Graph(HashMap::new());
+        for (a, b) in edges {
+            graph.add_edge(a, b);
         }
-        None
+        graph
     }
 
-    fn nconnections(&self, start: &N) -> usize {
-        let mut nconnections = 0;
-
-        let mut levels = HashMap::new();
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-
-        levels.insert(start, 0);
-        queue.push_back(start);
-        visited.insert(start);
+    /// Adds an edge from `a` to `b`. For an undirected graph, add one each way.
+    fn add_edge(&mut self, a: N, b: N) {
+        self.0.entry(a).or_default().push(b);
+    }
+}
 
-        while let Some(ref node) = queue.pop_front() {
-            if let Some(children) = self.0.get(node) {
-                for child in children {
-                    if !visited.contains(child) {
-                        let level = levels.get(node).unwrap() + 1;
-                        levels.insert(child, level);
+impl<N> Graph<N>
+where
+    N: Clone + Eq + Hash,
+{
+    fn shortest_distance(&self, a: &N, b: &N) -> Option<usize> {
+        self.shortest_path(a, b).map(|path| path.len() - 1)
+    }
 
-                        nconnections += level;
+    /// The nodes along a shortest path from `a` to `b`, including both ends.
+    fn shortest_path(&self, a: &N, b: &N) -> Option<Vec<N>> {
+        search::shortest_path(a.clone(), b, |node| self.children(node))
+    }
 
-                        visited.insert(child);
-                        queue.push_back(child);
-                    }
-                }
-            }
-        }
+    /// Sum of the distances from `start` to every node reachable from it.
+    fn nconnections(&self, start: &N) -> usize {
+        search::bfs(start.clone(), |node| self.children(node))
+            .values()
+            .sum()
+    }
 
-        nconnections
+    fn children<'a>(&'a self, node: &N) -> impl Iterator<Item = N> + 'a {
+        self.0.get(node).into_iter().flatten().cloned()
     }
 }
 
-fn parse_input<R>(mut reader: R) -> Result<(Graph<usize>, Graph<usize>, Ids), Error>
+/// Names the bodies along `path`, e.g. `YOU -> K -> SAN`.
+fn describe_path(path: &[usize], ids: &Ids) -> String {
+    let names = ids
+        .iter()
+        .map(|(name, id)| (*id, name.as_str()))
+        .collect::<HashMap<_, _>>();
+    path.iter()
+        .map(|id| names.get(id).copied().unwrap_or("?"))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+fn parse_input<R>(reader: R) -> Result<(Graph<usize>, Graph<usize>, Ids), Error>
 where
     R: io::BufRead,
 {
     let mut directed = Graph::default();
     let mut undirected = Graph::default();
 
-    let mut buffer = String::new();
     let mut id = 0;
     let mut ids: HashMap<String, usize> = HashMap::new();
-    loop {
-        if reader.read_line(&mut buffer)? == 0 {
-            break;
-        }
-
-        let line = buffer.trim();
-        let mut iter = line.split(')').map(|s| s.trim().to_string());
+    // The body each body orbits, to check that the orbits form a forest.
+    let mut parents: HashMap<usize, usize> = HashMap::new();
+    utils::for_each_line(reader, |_, line| {
+        let mut iter = line.split(')').map(|s| s.trim());
         let parent = iter
             .next()
             .ok_or_else(|| error!("Unable to parse input line {}", line))?;
@@ -151,26 +155,31 @@ where
             .next()
             .ok_or_else(|| error!("Unable to parse input line {}", line))?;
 
-        let id_parent = *ids.entry(parent).or_insert_with(|| id);
-        let id_child = *ids.entry(child).or_insert_with(|| id + 1);
+        let id_parent = *ids.entry(parent.to_string()).or_insert_with(|| id);
+        let id_child = *ids.entry(child.to_string()).or_insert_with(|| id + 1);
         id += 2;
 
-        directed
-            .entry(id_parent)
-            .or_insert_with(Vec::new)
-            .push(id_child);
-
-        undirected
-            .entry(id_parent)
-            .or_insert_with(Vec::new)
-            .push(id_child);
-        undirected
-            .entry(id_child)
-            .or_insert_with(Vec::new)
-            .push(id_parent);
-
-        buffer.clear();
-    }
+        if parents.insert(id_child, id_parent).is_some() {
+            bail!("Invalid input. {} orbits more than one body.", child);
+        }
+        let mut ancestor = Some(&id_parent);
+        while let Some(&a) = ancestor {
+            if a == id_child {
+                bail!(
+                    "Invalid input. {} orbits {}, which already orbits it, so the orbits form a cycle.",
+                    child,
+                    parent
+                );
+            }
+            ancestor = parents.get(&a);
+        }
+
+        directed.add_edge(id_parent, id_child);
+        undirected.add_edge(id_parent, id_child);
+        undirected.add_edge(id_child, id_parent);
+
+        Ok(())
+    })?;
 
     Ok((directed, undirected, ids))
 }
@@ -204,8 +213,48 @@ mod tests {
             let id_san = ids.get("SAN").unwrap();
             let dist = undirected.shortest_distance(id_you, id_san).unwrap();
             assert_eq!(dist, 6);
+
+            let path = undirected.shortest_path(id_you, id_san).unwrap();
+            assert_eq!(
+                describe_path(&path, &ids),
+                "YOU -> K -> J -> E -> D -> I -> SAN"
+            );
         }
 
         utils::tests::test_full_problem(6, run, "241064", "418");
     }
+
+    #[test]
+    fn test_06_cycles() {
+        let parse = |input: &str| parse_input(io::BufReader::new(input.as_bytes()));
+        let test_cases = &[
+            (
+                "A)B\nB)A",
+                "Invalid input. A orbits B, which already orbits it, so the orbits form a cycle.",
+            ),
+            (
+                "A)B\nB)C\nC)A",
+                "Invalid input. A orbits C, which already orbits it, so the orbits form a cycle.",
+            ),
+            (
+                "A)A",
+                "Invalid input. A orbits A, which already orbits it, so the orbits form a cycle.",
+            ),
+            ("A)C\nB)C", "Invalid input. C orbits more than one body."),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(&parse(input).unwrap_err().to_string(), expected);
+        }
+        assert!(parse("COM)A\nB)C\nA)B").is_ok());
+    }
+
+    #[test]
+    fn test_06_from_edges() {
+        let graph = Graph::from_edges(vec![("COM", "B"), ("B", "C"), ("B", "D"), ("D", "E")]);
+        assert_eq!(graph.nconnections(&"COM"), 1 + 2 + 2 + 3);
+        assert_eq!(graph.nconnections(&"D"), 1);
+        assert_eq!(graph["B"], vec!["C", "D"]);
+        assert_eq!(graph.shortest_distance(&"COM", &"E"), Some(3));
+        assert_eq!(graph.shortest_distance(&"E", &"COM"), None);
+    }
 }
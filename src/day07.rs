@@ -1,50 +1,185 @@
 use std::io;
+use std::ops::Range;
 use std::sync::Barrier;
 
 use crossbeam::{channel, thread};
 use itertools::Itertools;
 
-use crate::computer::{Channel, ComputerMT, Queue, Rom};
+use crate::computer::{Channel, ComputerMT, ComputerST, Hooks, Queue, Rom, State};
 use crate::error::Error;
 use crate::utils::math;
 
+/// Most amplifiers `solve` will chain together. Every permutation of phase settings
+/// is tried, so the work grows factorially with the number of amplifiers.
+const MAX_AMPLIFIERS: usize = 8;
+
+/// Most orderings of phase settings `solve` will try for each part.
+const MAX_PERMUTATIONS: usize = 1_000_000;
+
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
-    let ncomputers = 5;
-    let nchannels = math::fact(ncomputers)?;
+    run_with_hooks(reader, &Hooks::default())
+}
 
-    let barrier = Barrier::new(ncomputers);
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    let rom = Rom::from_reader(reader)?;
+    let (answer1, answer2) = solve_with_hooks(&rom, 5, 0..5, 5..10, hooks)?;
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Like `run`, but runs the amplifiers cooperatively on the current thread instead of
+/// on a thread each.
+pub fn run_single_threaded<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
     let rom = Rom::from_reader(reader)?;
+    let answer1 = max_signal_single_threaded(&rom, 5, 0..5)?;
+    let answer2 = max_signal_single_threaded(&rom, 5, 5..10)?;
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Finds the highest thruster signal over every ordering of phase settings drawn
+/// from `phases`, running the amplifiers in turn, each until it needs input it
+/// doesn't have, and passing its outputs along by hand.
+fn max_signal_single_threaded(
+    rom: &Rom,
+    ncomputers: usize,
+    phases: Range<i64>,
+) -> Result<i64, Error> {
+    let mut best = None;
+    for phase_settings in phases.permutations(ncomputers) {
+        let mut computers = phase_settings
+            .iter()
+            .map(|&phase_setting| {
+                let mut computer = ComputerST::new(rom);
+                computer.input_mut().push_back(phase_setting);
+                computer
+            })
+            .collect::<Vec<_>>();
+        computers[0].input_mut().push_back(0);
+
+        let mut signal = None;
+        loop {
+            let mut progressed = false;
+            for i in 0..ncomputers {
+                let (outputs, _) = computers[i].execute_until_input()?;
+                progressed |= !outputs.is_empty();
+                for output in outputs {
+                    if i == ncomputers - 1 {
+                        signal = Some(output);
+                    }
+                    computers[(i + 1) % ncomputers]
+                        .input_mut()
+                        .push_back(output);
+                }
+            }
+            if computers[ncomputers - 1].state() == Some(State::Done) {
+                break;
+            }
+            if !progressed {
+                bail!("Amplifiers stopped producing outputs before the last one halted.");
+            }
+        }
+
+        let signal = signal.ok_or_else(|| error!("Last amplifier halted without output."))?;
+        best = best.max(Some(signal));
+    }
+    best.ok_or_else(|| error!("No phase settings to try."))
+}
+
+/// Finds the highest thruster signal from a chain of `ncomputers` amplifiers running
+/// `rom`, trying every ordering of distinct phase settings drawn from `part1_phases`
+/// (for part 1) and from `part2_phases` (for part 2, where the amplifiers run in a
+/// feedback loop).
+pub fn solve(
+    rom: &Rom,
+    ncomputers: usize,
+    part1_phases: Range<i64>,
+    part2_phases: Range<i64>,
+) -> Result<(i64, i64), Error> {
+    solve_with_hooks(
+        rom,
+        ncomputers,
+        part1_phases,
+        part2_phases,
+        &Hooks::default(),
+    )
+}
+
+fn solve_with_hooks(
+    rom: &Rom,
+    ncomputers: usize,
+    part1_phases: Range<i64>,
+    part2_phases: Range<i64>,
+    hooks: &Hooks,
+) -> Result<(i64, i64), Error> {
+    if ncomputers == 0 || ncomputers > MAX_AMPLIFIERS {
+        bail!(
+            "Number of amplifiers must be between 1 and {}, but got {}.",
+            MAX_AMPLIFIERS,
+            ncomputers
+        );
+    }
+    for phases in &[&part1_phases, &part2_phases] {
+        if phases.end - phases.start < ncomputers as i64 {
+            bail!(
+                "Phase settings {:?} are too few to give each of {} amplifiers a distinct one.",
+                phases,
+                ncomputers
+            );
+        }
+        let nphases = (phases.end - phases.start) as usize;
+        match math::npermutations(nphases, ncomputers) {
+            Ok(n) if n <= MAX_PERMUTATIONS => (),
+            _ => bail!(
+                "Phase settings {:?} give more than {} orderings for {} amplifiers.",
+                phases,
+                MAX_PERMUTATIONS,
+                ncomputers
+            ),
+        }
+    }
+
+    let barrier = Barrier::new(ncomputers);
 
     let (answer1, answer2) = thread::scope(|s| {
-        let (tx_output, rx_output) = channel::bounded(nchannels);
+        // Every job is queued up before any result is read, so results need an
+        // unbounded channel. Jobs are handed out one at a time, though, so that only
+        // a few computers exist at once however many orderings there are.
+        let (tx_output, rx_output) = channel::unbounded();
 
         let mut handles = Vec::new();
         let mut senders = Vec::new();
         for i in 0..ncomputers {
-            let (tx_input, rx_input) = channel::bounded(nchannels);
+            let (tx_input, rx_input) = channel::bounded(1);
             senders.push(tx_input);
 
             let barrier = &barrier;
-            let rom = &rom;
             let tx_output = tx_output.clone();
 
             let handle = s.spawn(move |_| {
                 while let Ok((part, phase_setting, input, output)) = rx_input.recv() {
                     let mut computer = ComputerMT::new(rom, input, output);
+                    computer.set_label(format!("amplifier {}", i));
+                    hooks.apply(&mut computer);
 
-                    computer.input_mut().enqueue(phase_setting);
+                    computer.input_mut().enqueue(phase_setting)?;
                     if i == 0 {
-                        computer.input_mut().enqueue(0);
+                        computer.input_mut().enqueue(0)?;
                     }
 
                     barrier.wait();
                     computer.run()?;
                     barrier.wait();
 
-                    if i == 4 {
+                    if i == ncomputers - 1 {
                         let answer = computer.output_mut().dequeue()?;
                         tx_output.send((part, answer)).unwrap();
                     }
@@ -54,8 +189,8 @@ where
             handles.push(handle);
         }
 
-        for (part, range) in (&[(0..5), (5..10)]).iter().cloned().enumerate() {
-            for phase_settings in range.map(|i| i as i64).permutations(ncomputers) {
+        for (part, range) in IntoIterator::into_iter([part1_phases, part2_phases]).enumerate() {
+            for phase_settings in range.permutations(ncomputers) {
                 let channels = (0..ncomputers)
                     .map(|_| Channel::default())
                     .collect::<Vec<_>>();
@@ -101,7 +236,7 @@ where
     })
     .unwrap()?;
 
-    Ok((answer1.to_string(), answer2.to_string()))
+    Ok((answer1, answer2))
 }
 
 #[cfg(test)]
@@ -110,8 +245,98 @@ mod tests {
 
     use crate::utils;
 
+    #[test]
+    fn test_solve() {
+        let rom = |s: &str| Rom::from_reader(s.as_bytes()).unwrap();
+
+        let test_cases = &[
+            ("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", 43210),
+            (
+                "3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0",
+                54321,
+            ),
+            (
+                "3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,\
+                 1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0",
+                65210,
+            ),
+        ];
+        for (program, expected) in test_cases {
+            let (actual, _) = solve(&rom(program), 5, 0..5, 5..10).unwrap();
+            assert_eq!(actual, *expected);
+        }
+
+        let test_cases = &[
+            (
+                "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,\
+                 27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+                139629729,
+            ),
+            (
+                "3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,\
+                 -5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,\
+                 53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10",
+                18216,
+            ),
+        ];
+        for (program, expected) in test_cases {
+            // These programs are only meant for the feedback loop's phase settings, so
+            // use those for both parts.
+            let (_, actual) = solve(&rom(program), 5, 5..10, 5..10).unwrap();
+            assert_eq!(actual, *expected);
+        }
+
+        // The first example computes `10 * input + phase`, so with two amplifiers the
+        // best phase settings are 4 and then 3.
+        let program = rom("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        assert_eq!(solve(&program, 2, 0..5, 0..5).unwrap(), (43, 43));
+        // 30! overflows a usize, but there are only 870 orderings of two of them.
+        assert_eq!(solve(&program, 2, 0..30, 0..5).unwrap(), (318, 43));
+
+        let e = solve(&program, 5, 0..30, 0..5).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Phase settings 0..30 give more than 1000000 orderings for 5 amplifiers."
+        );
+        // Ranges whose factorial overflows are still checked against the limit.
+        let e = solve(&program, 2, 0..5, 0..i64::MAX).unwrap_err();
+        assert!(e.to_string().contains("more than 1000000 orderings"));
+        assert!(solve(&program, 0, 0..5, 5..10).is_err());
+        assert!(solve(&program, MAX_AMPLIFIERS + 1, 0..20, 20..40).is_err());
+        assert!(solve(&program, 5, 0..4, 5..10).is_err());
+    }
+
+    #[test]
+    fn test_solve_with_hooks() {
+        use std::sync::{Arc, Mutex};
+
+        let ninstructions = Arc::new(Mutex::new(0));
+        let counter = Arc::clone(&ninstructions);
+        let hooks = Hooks::with_trace(Box::new(move |_, _: &str| *counter.lock().unwrap() += 1));
+
+        // Two inputs, a multiply, an add, an output and a halt for each amplifier,
+        // with two amplifiers and two orderings of their phase settings in each part.
+        let program =
+            Rom::from_reader("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0".as_bytes()).unwrap();
+        assert_eq!(
+            solve_with_hooks(&program, 2, 0..2, 0..2, &hooks).unwrap(),
+            (10, 10)
+        );
+        assert_eq!(*ninstructions.lock().unwrap(), 6 * 2 * 2 * 2);
+    }
+
     #[test]
     fn test_07() {
         utils::tests::test_full_problem(7, run, "43812", "59597414");
     }
+
+    #[test]
+    fn test_07_single_threaded() {
+        if let (Some(a), Some(b)) = (utils::tests::data_file(7), utils::tests::data_file(7)) {
+            assert_eq!(run(a).unwrap(), run_single_threaded(b).unwrap());
+        }
+
+        let rom = Rom::from_reader("3,0,99".as_bytes()).unwrap();
+        assert!(max_signal_single_threaded(&rom, 2, 0..2).is_err());
+    }
 }
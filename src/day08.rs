@@ -1,6 +1,7 @@
 use std::io;
 
 use crate::error::Error;
+use crate::utils::grid::Grid;
 
 const ROWS: usize = 6;
 const COLS: usize = 25;
@@ -45,19 +46,15 @@ where
             state
         });
 
-    let mut iter = image.iter();
-    let mut answer2 = String::new();
-    for _row in 0..ROWS {
-        for _col in 0..COLS {
-            match iter.next() {
-                Some(0) => answer2.push('\u{2585}'),
-                Some(1) => answer2.push(' '),
-                Some(_) => bail!("Bad input: Found digit that is neither 0 nor 1"),
-                None => bail!("Bad input. Must contain {} rows and {} columns", ROWS, COLS),
-            }
-        }
-        answer2.push('\n');
-    }
+    let pixels = image
+        .iter()
+        .map(|pixel| match pixel {
+            0 => Ok('\u{2585}'),
+            1 => Ok(' '),
+            _ => bail!("Bad input: Found digit that is neither 0 nor 1"),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let answer2 = Grid::from_vec(COLS, ROWS, pixels)?.to_string();
 
     Ok((answer1.to_string(), answer2))
 }
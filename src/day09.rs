@@ -1,21 +1,31 @@
 use std::io;
 
-use crate::computer::{ComputerST, Queue, Rom};
+use crate::computer::{ComputerST, Hooks, Queue, Rom};
 use crate::error::Error;
 
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(reader, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(reader)?;
 
     let mut computer = ComputerST::new(&rom);
-    computer.input_mut().enqueue(1);
+    hooks.apply(&mut computer);
+    computer.feed(&[1])?;
     computer.run()?;
     let answer1 = computer.output_mut().dequeue()?;
 
     let mut computer = ComputerST::new(&rom);
-    computer.input_mut().enqueue(2);
+    hooks.apply(&mut computer);
+    computer.feed(&[2])?;
     computer.run()?;
     let answer2 = computer.output_mut().dequeue()?;
 
@@ -102,8 +102,19 @@ struct Asteroid {
 }
 
 impl Asteroid {
+    /// The asteroid at `point`, as seen from the laser. `point` must differ from
+    /// `laser`, since an asteroid at the laser's own position has no angle; with that
+    /// ruled out, `atan2` always returns a finite angle.
     fn new(laser: Point, point: Point) -> Result<Self, Error> {
-        let (x, y) = (point.x() - laser.x(), point.y() - laser.y());
+        if point == laser {
+            bail!(
+                "Cannot aim the laser at ({}, {}), since that is where the laser is.",
+                point.x(),
+                point.y()
+            );
+        }
+        let delta = point - laser;
+        let (x, y) = (delta.x(), delta.y());
         let angle = (y as f64).atan2(x as f64);
         let angle = polar_coordinates_transformation(angle);
         let distance_squared = (x * x + y * y) as u64;
@@ -117,18 +128,15 @@ impl Asteroid {
 
 impl PartialOrd for Asteroid {
     fn partial_cmp(&self, other: &Asteroid) -> Option<Ordering> {
-        match self.angle.partial_cmp(&other.angle) {
-            Some(Ordering::Less) => Some(Ordering::Less),
-            Some(Ordering::Greater) => Some(Ordering::Greater),
-            Some(Ordering::Equal) => Some(self.distance_squared.cmp(&other.distance_squared)),
-            None => unreachable!(),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Asteroid {
     fn cmp(&self, other: &Asteroid) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.angle
+            .cmp(&other.angle)
+            .then_with(|| self.distance_squared.cmp(&other.distance_squared))
     }
 }
 
@@ -170,7 +178,8 @@ pub struct Direction(F64);
 
 impl Direction {
     fn new(origin: Point, other: Point) -> Result<Self, Error> {
-        let (x, y) = (other.x() - origin.x(), other.y() - origin.y());
+        let delta = other - origin;
+        let (x, y) = (delta.x(), delta.y());
         let angle = (y as f64).atan2(x as f64);
         Ok(Direction(F64::try_from(angle)?))
     }
@@ -203,6 +212,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_10_asteroid_at_laser() {
+        let laser = Point::new(3, 4);
+        let e = Asteroid::new(laser, laser).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Cannot aim the laser at (3, 4), since that is where the laser is."
+        );
+
+        // A duplicated asteroid is skipped along with the laser rather than panicking.
+        let points = [laser, Point::new(3, 0), laser];
+        let mut asteroids = part2(laser, &points).unwrap();
+        assert_eq!(asteroids.next().unwrap().point, Point::new(3, 0));
+        assert!(asteroids.next().is_none());
+    }
+
     #[test]
     fn test_10() {
         utils::tests::test_full_problem(10, run, "260", "608");
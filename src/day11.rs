@@ -6,36 +6,79 @@ use std::io;
 use crossbeam::channel::{Receiver, Sender};
 use crossbeam::thread;
 
-use crate::computer::{Channel, ComputerMT, Rom};
+use crate::computer::{Channel, ComputerMT, Hooks, Rom};
 use crate::error::Error;
+use crate::utils::direction::{Direction, Turn};
+use crate::utils::grid::Grid;
 use crate::utils::Vec2;
 
 type Point = Vec2<i64>;
 
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(reader, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(reader)?;
 
     // Part 1
-    let robot = Robot::run(&rom, Color::Black)?;
-    let answer1 = robot.grid.keys().count();
+    let answer1 = PaintResult(Robot::run(&rom, Color::Black, hooks)?).npainted();
 
     // Part 2
-    let robot = Robot::run(&rom, Color::White)?;
-    let answer2 = robot.to_string();
+    let answer2 = PaintResult(Robot::run(&rom, Color::White, hooks)?).render();
 
     Ok((answer1.to_string(), answer2))
 }
 
+/// Runs the painting robot over a hull whose starting panel has the given color.
+pub fn paint(rom: &Rom, start: Color) -> Result<PaintResult, Error> {
+    let robot = Robot::run(rom, start, &Hooks::default())?;
+    Ok(PaintResult(robot))
+}
+
+pub struct PaintResult(Robot);
+
+impl PaintResult {
+    /// Number of panels painted at least once (including the starting panel).
+    pub fn npainted(&self) -> usize {
+        self.0.grid.len()
+    }
+
+    /// Every panel painted at least once (including the starting panel) as `(x, y)`
+    /// along with its current color.
+    pub fn painted_cells(&self) -> impl Iterator<Item = ((i64, i64), Color)> + '_ {
+        self.0
+            .painted_cells()
+            .map(|(point, color)| ((point.x(), point.y()), color))
+    }
+
+    /// The lower-left and upper-right corners, as `(x, y)`, of the smallest rectangle
+    /// containing every painted panel.
+    pub fn bounds(&self) -> ((i64, i64), (i64, i64)) {
+        let (min, max) = self.0.bounds();
+        ((min.x(), min.y()), (max.x(), max.y()))
+    }
+
+    /// Renders the hull with white panels drawn as `#` and black panels as spaces.
+    pub fn render(&self) -> String {
+        self.0.to_string()
+    }
+}
+
 struct Robot {
     grid: HashMap<Point, Color>,
     location: Location,
 }
 
 impl Robot {
-    fn run(rom: &Rom, color: Color) -> Result<Self, Error> {
+    fn run(rom: &Rom, color: Color, hooks: &Hooks) -> Result<Self, Error> {
         thread::scope(|s| {
             let mut robot = Self {
                 grid: HashMap::default(),
@@ -51,22 +94,46 @@ impl Robot {
             let (_, receiver) = output.clone().into_parts();
             let handle = s.spawn(move |_| {
                 let mut computer = ComputerMT::new(rom, input, output);
+                hooks.apply(&mut computer);
                 computer.run()?;
                 Ok::<_, Error>(())
             });
 
+            let mut nsteps = 0;
             loop {
                 if robot.step(&sender, &receiver)?.is_none() {
                     break;
                 }
+                nsteps += 1;
             }
 
             handle.join().unwrap()?;
+
+            if nsteps == 0 {
+                bail!("Invalid robot program. It halted without issuing any paint commands.");
+            }
+
             Ok(robot)
         })
         .unwrap()
     }
 
+    /// Every panel painted at least once (including the starting panel) along with
+    /// its current color.
+    fn painted_cells(&self) -> impl Iterator<Item = (Point, Color)> + '_ {
+        self.grid.iter().map(|(&point, &color)| (point, color))
+    }
+
+    /// The lower-left and upper-right corners of the smallest rectangle containing
+    /// every painted panel.
+    fn bounds(&self) -> (Point, Point) {
+        let min_x = self.grid.keys().map(|p| p.x()).min().unwrap();
+        let max_x = self.grid.keys().map(|p| p.x()).max().unwrap();
+        let min_y = self.grid.keys().map(|p| p.y()).min().unwrap();
+        let max_y = self.grid.keys().map(|p| p.y()).max().unwrap();
+        (Point::new(min_x, min_y), Point::new(max_x, max_y))
+    }
+
     fn step(
         &mut self,
         sender: &Sender<i64>,
@@ -96,40 +163,28 @@ impl Robot {
 
 impl fmt::Display for Robot {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let min_x = self.grid.keys().map(|p| p.x()).min().unwrap();
-        let max_x = self.grid.keys().map(|p| p.x()).max().unwrap();
-        let min_y = self.grid.keys().map(|p| p.y()).min().unwrap();
-        let max_y = self.grid.keys().map(|p| p.y()).max().unwrap();
-
-        let rows = (max_y - min_y) as usize + 1;
-        let cols = (max_x - min_x) as usize + 1;
-
-        let len = rows * (cols + 1);
-
-        let mut buf = vec![b' '; len];
-        for point in self
-            .grid
-            .iter()
-            .filter(|(_, color)| **color == Color::White)
-            .map(|(point, _)| point)
+        let (min, max) = self.bounds();
+        let width = (max.x() - min.x()) as usize + 1;
+        let height = (max.y() - min.y()) as usize + 1;
+
+        // The hull's y axis points up, but the grid's points down.
+        let mut grid = Grid::new(width, height, ' ');
+        for (point, _) in self
+            .painted_cells()
+            .filter(|(_, color)| *color == Color::White)
         {
-            let x = (point.x() - min_x) as usize;
-            let y = (point.y() - min_y) as usize;
-            buf[(rows - 1 - y) * cols + x] = b'#';
-        }
-
-        for row in 0..(rows - 1) {
-            buf[row * cols + cols - 1] = b'\n';
+            let x = (point.x() - min.x()) as usize;
+            let y = (max.y() - point.y()) as usize;
+            *grid.get_mut(x, y).unwrap() = '#';
         }
 
-        let s = String::from_utf8(buf).unwrap();
-        write!(f, "{}", s)
+        write!(f, "{}", grid)
     }
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 #[repr(u8)]
-enum Color {
+pub enum Color {
     Black = 0,
     White = 1,
 }
@@ -147,31 +202,6 @@ impl TryFrom<i64> for Color {
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-enum Direction {
-    North,
-    South,
-    East,
-    West,
-}
-
-impl Direction {
-    fn next(self, turn: Turn) -> Direction {
-        use self::Direction::*;
-        use self::Turn::*;
-        match (self, turn) {
-            (North, Left) => West,
-            (North, Right) => East,
-            (South, Left) => East,
-            (South, Right) => West,
-            (East, Left) => North,
-            (East, Right) => South,
-            (West, Left) => South,
-            (West, Right) => North,
-        }
-    }
-}
-
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 struct Location {
     point: Point,
@@ -180,30 +210,14 @@ struct Location {
 
 impl Location {
     fn next(&self, turn: Turn) -> Location {
-        use self::Direction::*;
-        let (x, y) = (self.point.x(), self.point.y());
-        let next_direction = self.direction.next(turn);
-        let next_point = match next_direction {
-            North => (x, y + 1),
-            South => (x, y - 1),
-            East => (x + 1, y),
-            West => (x - 1, y),
-        }
-        .into();
+        let direction = self.direction.turn(turn);
         Self {
-            direction: next_direction,
-            point: next_point,
+            direction,
+            point: self.point + direction.delta(),
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
-#[repr(u8)]
-enum Turn {
-    Left = 0,
-    Right = 1,
-}
-
 impl TryFrom<i64> for Turn {
     type Error = Error;
 
@@ -219,17 +233,62 @@ impl TryFrom<i64> for Turn {
 
 #[cfg(test)]
 mod tests {
-    use std::fs;
-
     use super::*;
 
+    use crate::utils;
+
     #[test]
     fn test_11() {
-        let file = fs::File::open("data/11.txt").unwrap();
-        let reader = io::BufReader::new(file);
+        let Some(reader) = utils::tests::data_file(11) else {
+            return;
+        };
         let rom = Rom::from_reader(reader).unwrap();
-        let robot = Robot::run(&rom, Color::Black).unwrap();
+        let robot = Robot::run(&rom, Color::Black, &Hooks::default()).unwrap();
         let actual = robot.grid.keys().count();
         assert_eq!(actual, 2293);
     }
+
+    #[test]
+    fn test_painted_cells() {
+        let Some(reader) = utils::tests::data_file(11) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+
+        let result = paint(&rom, Color::Black).unwrap();
+        assert_eq!(result.painted_cells().count(), 2293);
+
+        let result = paint(&rom, Color::White).unwrap();
+        let ((min_x, min_y), (max_x, max_y)) = result.bounds();
+        assert!(result
+            .painted_cells()
+            .all(|((x, y), _)| min_x <= x && x <= max_x && min_y <= y && y <= max_y));
+        // Part two's registration identifier is six panels tall.
+        assert_eq!(max_y - min_y + 1, 6);
+        let white = result
+            .painted_cells()
+            .filter(|(_, color)| *color == Color::White)
+            .count();
+        assert_eq!(white, result.render().matches('#').count());
+    }
+
+    #[test]
+    fn test_paint() {
+        let Some(reader) = utils::tests::data_file(11) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        assert_eq!(paint(&rom, Color::Black).unwrap().npainted(), 2293);
+        assert_eq!(paint(&rom, Color::White).unwrap().npainted(), 249);
+    }
+
+    #[test]
+    fn test_11_never_moves() {
+        let reader = io::BufReader::new("99".as_bytes());
+        let rom = Rom::from_reader(reader).unwrap();
+        let e = Robot::run(&rom, Color::Black, &Hooks::default())
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("without issuing any paint commands"));
+    }
 }
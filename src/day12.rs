@@ -1,80 +1,123 @@
+use std::array;
 use std::cell::RefCell;
-use std::cmp::Ordering;
-use std::collections::HashSet;
 use std::io;
-use std::mem;
 use std::ops::{Deref, DerefMut};
 
 use crate::error::Error;
-use crate::utils::{math::lcm, Vec3};
+use crate::nbody::{Body, System};
+use crate::utils::{math::lcm_all, Vec3};
 
-#[cfg(not(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-)))]
 use self::normal::Moon;
 
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
-use self::simd::Moon;
-
 const PAIRS: [(usize, usize); 6] = [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)];
 
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
-    let mut moons = parse_input(reader)?;
+    let system = parse_input(reader)?;
+    let mut moons = Moons::from_system(&system)?;
 
-    let mut nsteps = 0;
+    let (energy, periods) = simulate(&mut moons, 1000);
+
+    let answer1 = energy.ok_or_else(|| error!("Did not complete 1000 steps."))?;
+    let answer2 = lcm_all(periods.iter().copied())?;
 
-    let mut seen = [HashSet::new(), HashSet::new(), HashSet::new()];
-    let mut counts = [None, None, None];
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Steps the moons until the period of every axis is known, returning those periods
+/// along with the total energy after `nsteps_energy` steps (if the simulation got
+/// that far).
+fn simulate(moons: &mut Moons, nsteps_energy: usize) -> (Option<u64>, [u64; 3]) {
+    simulate_with(moons, nsteps_energy, Moons::step)
+}
 
-    let mut answer1 = Err(error!("Did not complete 1000 steps."));
+/// Like `simulate`, but advancing the moons with the given step function.
+fn simulate_with<F>(moons: &mut Moons, nsteps_energy: usize, mut step: F) -> (Option<u64>, [u64; 3])
+where
+    F: FnMut(&mut Moons),
+{
+    let initial = moons.state();
+    let mut cycles = [
+        Brent::new(initial[0]),
+        Brent::new(initial[1]),
+        Brent::new(initial[2]),
+    ];
+
+    let mut energy = None;
+    let mut nsteps = 0;
     loop {
+        step(moons);
+        nsteps += 1;
+
+        if nsteps == nsteps_energy {
+            energy = Some(moons.energy());
+        }
+
         let state = moons.state();
         for coord in 0..3 {
-            if counts[coord].is_none() && !seen[coord].insert(state[coord]) {
-                counts[coord] = Some(nsteps)
-            }
+            cycles[coord].observe(state[coord]);
         }
-        let done = counts.iter().all(|count| count.is_some());
-        if done {
+        if cycles.iter().all(|cycle| cycle.period.is_some()) {
             break;
         }
+    }
 
-        moons.step();
-        nsteps += 1;
+    let periods = [
+        cycles[0].period.unwrap(),
+        cycles[1].period.unwrap(),
+        cycles[2].period.unwrap(),
+    ];
+    (energy, periods)
+}
+
+/// Brent's cycle detection, fed one state at a time. Only ever holds a single saved
+/// state rather than every state seen.
+struct Brent<T> {
+    tortoise: T,
+    power: u64,
+    lam: u64,
+    period: Option<u64>,
+}
 
-        if nsteps == 1000 {
-            answer1 = Ok(moons.energy());
+impl<T> Brent<T>
+where
+    T: Copy + PartialEq,
+{
+    fn new(initial: T) -> Self {
+        Self {
+            tortoise: initial,
+            power: 1,
+            lam: 1,
+            period: None,
         }
     }
 
-    let answer2 = lcm(
-        lcm(counts[0].unwrap(), counts[1].unwrap())?,
-        counts[2].unwrap(),
-    )?;
-
-    Ok((answer1?.to_string(), answer2.to_string()))
+    /// Feeds the next state in the sequence (the "hare").
+    fn observe(&mut self, hare: T) {
+        if self.period.is_some() {
+            return;
+        }
+        if self.tortoise == hare {
+            self.period = Some(self.lam);
+            return;
+        }
+        if self.power == self.lam {
+            self.tortoise = hare;
+            self.power *= 2;
+            self.lam = 0;
+        }
+        self.lam += 1;
+    }
 }
 
-fn parse_input<R>(reader: R) -> Result<Moons, Error>
+pub fn parse_input<R>(reader: R) -> Result<System, Error>
 where
     R: io::BufRead,
 {
-    // safety: This is safe because the code below ensures that by the
-    // time we would ever try to touch the moons array, all values inside
-    // will contain specific values that we have written to it.
-    let mut moons: [RefCell<Moon>; 4] = unsafe { mem::MaybeUninit::uninit().assume_init() };
-    let mut i = 0;
+    let mut bodies = Vec::new();
     for res in reader.lines() {
-        if i > 3 {
-            bail!("Can only support exactly 4 moons.");
-        }
         let line = res?;
         let line = line.trim();
         let mut pos: [i64; 3] = [0i64; 3];
@@ -94,101 +137,141 @@ where
         if j != 3 {
             bail!("Found {} coordinate, but need 3", j);
         }
-        let moon = Moon::new(pos, Vec3::default());
-        moons[i] = RefCell::new(moon);
-        i += 1;
+        bodies.push(Body::new(pos, [0; 3]));
     }
-    if i != 4 {
-        bail!("Can only support exactly 4 moons");
-    }
-
-    Ok(Moons(moons))
+    Ok(System::new(bodies))
 }
 
+/// Exactly four moons, stepped with either a scalar or an AVX2 implementation.
 #[derive(Clone, Debug, Eq, PartialEq)]
-struct Moons([RefCell<Moon>; 4]);
+pub struct Moons {
+    moons: [RefCell<Moon>; 4],
+    /// Total velocity of the moons when they were created, which stepping conserves.
+    momentum: Vec3<i64>,
+}
 
 impl Moons {
-    fn energy(&self) -> u64 {
-        let mut total = 0;
-        for moon in self.iter() {
-            let moon = moon.borrow();
-            let mut potential = 0;
-            let mut kinetic = 0;
-            for k in 0..3 {
-                potential += moon.pos()[k].abs() as u64;
-                kinetic += moon.vel()[k].abs() as u64;
+    pub fn from_system(system: &System) -> Result<Self, Error> {
+        if system.bodies().len() != 4 {
+            bail!("Can only support exactly 4 moons");
+        }
+        let bodies = system.bodies();
+        let moons = array::from_fn(|i| RefCell::new(Moon::new(bodies[i].pos(), bodies[i].vel())));
+        let momentum = Self::total_velocity(&moons);
+        Ok(Moons { moons, momentum })
+    }
+
+    fn total_velocity(moons: &[RefCell<Moon>]) -> Vec3<i64> {
+        moons
+            .iter()
+            .fold(Vec3::default(), |total, moon| total + *moon.borrow().vel())
+    }
+
+    /// Steps the moons with the AVX2 implementation if this CPU supports it, and
+    /// with the scalar implementation otherwise. Debug builds check the result with
+    /// `check_invariants`.
+    pub fn step(&mut self) {
+        self.step_best();
+        #[cfg(debug_assertions)]
+        {
+            if let Err(e) = self.check_invariants() {
+                panic!("{}", e);
             }
-            total += potential * kinetic;
         }
-        total
     }
 
-    pub(crate) fn state(&self) -> [[(i64, i64); 4]; 3] {
-        // safety: code below ensures we're filling uninitialized array with actual values
-        let mut a: [[(i64, i64); 4]; 3] = unsafe { mem::MaybeUninit::uninit().assume_init() };
-        for moon in 0..4 {
-            let state = self.0[moon].borrow().state();
-            for coord in 0..3 {
-                a[coord][moon] = state[coord];
+    fn step_best(&mut self) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                // safety: We've just checked that the CPU supports AVX2.
+                return unsafe { self.step_avx2() };
+            }
+        }
+        self.step_scalar()
+    }
+
+    /// Checks that the moons' total velocity is what it was when they were created.
+    /// Gravity pulls each pair of moons equally in opposite directions, so stepping
+    /// never changes it.
+    pub fn check_invariants(&self) -> Result<(), Error> {
+        let total = Self::total_velocity(&self.moons);
+        if total != self.momentum {
+            bail!(
+                "The moons' total velocity should stay {:?}, but it is {:?}.",
+                &*self.momentum,
+                &*total
+            );
+        }
+        Ok(())
+    }
+
+    /// Steps the moons with the AVX2 implementation, failing if this CPU doesn't
+    /// support it.
+    pub fn step_simd(&mut self) -> Result<(), Error> {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                // safety: We've just checked that the CPU supports AVX2.
+                unsafe { self.step_avx2() };
+                return Ok(());
             }
         }
-        a
+        bail!("AVX2 is not available on this CPU.")
+    }
+
+    pub fn energy(&self) -> u64 {
+        self.iter()
+            .map(|moon| {
+                let moon = moon.borrow();
+                moon.pos().abs_sum() as u64 * moon.vel().abs_sum() as u64
+            })
+            .sum()
+    }
+
+    pub(crate) fn state(&self) -> [[(i64, i64); 4]; 3] {
+        let states: [[(i64, i64); 3]; 4] = array::from_fn(|moon| self.moons[moon].borrow().state());
+        array::from_fn(|coord| array::from_fn(|moon| states[moon][coord]))
     }
 }
 
 impl Deref for Moons {
     type Target = [RefCell<Moon>];
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.moons
     }
 }
 
 impl DerefMut for Moons {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.moons
     }
 }
 
-#[cfg(not(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-)))]
 mod normal {
     use super::*;
 
     impl Moons {
-        pub(crate) fn step(&mut self) {
-            for (i, j) in PAIRS.iter() {
-                let moon_i = self.0.get(*i).unwrap();
-                let moon_j = self.0.get(*j).unwrap();
-                for k in 0..3 {
-                    let pos_i = moon_i.borrow().pos()[k];
-                    let pos_j = moon_j.borrow().pos()[k];
-                    match pos_i.cmp(&pos_j) {
-                        Ordering::Less => {
-                            moon_i.borrow_mut().vel_mut()[k] += 1;
-                            moon_j.borrow_mut().vel_mut()[k] -= 1;
-                        }
-                        Ordering::Greater => {
-                            moon_i.borrow_mut().vel_mut()[k] -= 1;
-                            moon_j.borrow_mut().vel_mut()[k] += 1;
-                        }
-                        Ordering::Equal => {}
-                    }
-                }
+        /// Steps the moons one pair at a time.
+        pub fn step_scalar(&mut self) {
+            for &(i, j) in PAIRS.iter() {
+                let mut moon_i = self.moons[i].borrow_mut();
+                let mut moon_j = self.moons[j].borrow_mut();
+                let diff = *moon_j.pos() - *moon_i.pos();
+                let pull = Vec3::new(diff.x().signum(), diff.y().signum(), diff.z().signum());
+                *moon_i.vel_mut() = *moon_i.vel() + pull;
+                *moon_j.vel_mut() = *moon_j.vel() - pull;
             }
-            for moon in self.iter_mut() {
-                for k in 0..3 {
-                    let vel = { moon.borrow().vel()[k] };
-                    moon.borrow_mut().pos_mut()[k] += vel;
-                }
+            for moon in self.iter() {
+                let mut moon = moon.borrow_mut();
+                let vel = *moon.vel();
+                *moon.pos_mut() = *moon.pos() + vel;
             }
         }
     }
 
     #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
-    pub(crate) struct Moon {
+    pub struct Moon {
         pos: Vec3<i64>,
         vel: Vec3<i64>,
     }
@@ -231,123 +314,183 @@ mod normal {
     }
 }
 
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod simd {
     #[cfg(target_arch = "x86")]
     use std::arch::x86::*;
     #[cfg(target_arch = "x86_64")]
     use std::arch::x86_64::*;
 
-    use lazy_static::lazy_static;
-
     use super::*;
 
-    lazy_static! {
-        static ref ONE: __m256i = unsafe { _mm256_set_epi64x(1, 1, 1, 0) };
-        static ref NEGATIVE_ONE: __m256i = unsafe { _mm256_set_epi64x(-1, -1, -1, 0) };
-    }
-
     impl Moons {
-        pub(crate) fn step(&mut self) {
-            for (i, j) in PAIRS.iter() {
-                let moon_i = self.0.get(*i).unwrap();
-                let moon_j = self.0.get(*j).unwrap();
-
-                let pos_i = moon_i.borrow().pos;
-                let pos_j = moon_j.borrow().pos;
+        /// Steps the moons with all three axes of a moon packed into one AVX2 register
+        /// (see `Vec3::to_m256_array` for the lane layout).
+        ///
+        /// safety: Must only be called on a CPU that supports AVX2.
+        #[target_feature(enable = "avx2")]
+        pub(crate) unsafe fn step_avx2(&mut self) {
+            let one = _mm256_set_epi64x(1, 1, 1, 0);
+            let negative_one = _mm256_set_epi64x(-1, -1, -1, 0);
+
+            let mut pos = [_mm256_setzero_si256(); 4];
+            let mut vel = [_mm256_setzero_si256(); 4];
+            for (i, moon) in self.iter().enumerate() {
+                let moon = moon.borrow();
+                let p = moon.pos().to_m256_array();
+                let v = moon.vel().to_m256_array();
+                pos[i] = _mm256_loadu_si256(p.as_ptr() as *const __m256i);
+                vel[i] = _mm256_loadu_si256(v.as_ptr() as *const __m256i);
+            }
 
+            for &(i, j) in PAIRS.iter() {
                 // Adding
-                let mask_gt = unsafe { _mm256_cmpgt_epi64(pos_i, pos_j) };
-                let operand_add = unsafe { _mm256_and_si256(mask_gt, *NEGATIVE_ONE) };
+                let mask_gt = _mm256_cmpgt_epi64(pos[i], pos[j]);
+                let operand_add = _mm256_and_si256(mask_gt, negative_one);
 
                 // Subtracting
-                let mask_lt = unsafe { _mm256_cmpgt_epi64(pos_j, pos_i) };
-                let operand_sub = unsafe { _mm256_and_si256(mask_lt, *ONE) };
+                let mask_lt = _mm256_cmpgt_epi64(pos[j], pos[i]);
+                let operand_sub = _mm256_and_si256(mask_lt, one);
 
-                let operand = unsafe { _mm256_or_si256(operand_add, operand_sub) };
-
-                let mut moon_ref = moon_i.borrow_mut();
-                let vel_ref = moon_ref.vel_mut();
-                *vel_ref = unsafe { _mm256_add_epi64(*vel_ref, operand) };
-
-                let mut moon_ref = moon_j.borrow_mut();
-                let vel_ref = moon_ref.vel_mut();
-                *vel_ref = unsafe { _mm256_sub_epi64(*vel_ref, operand) };
+                let operand = _mm256_or_si256(operand_add, operand_sub);
+                vel[i] = _mm256_add_epi64(vel[i], operand);
+                vel[j] = _mm256_sub_epi64(vel[j], operand);
             }
-            for moon in self.iter_mut() {
-                let new_pos = {
-                    let moon = moon.borrow();
-                    unsafe { _mm256_add_epi64(moon.pos, moon.vel) }
-                };
+
+            for (i, moon) in self.iter_mut().enumerate() {
+                let (mut p, mut v) = ([0i64; 4], [0i64; 4]);
+                _mm256_storeu_si256(
+                    p.as_mut_ptr() as *mut __m256i,
+                    _mm256_add_epi64(pos[i], vel[i]),
+                );
+                _mm256_storeu_si256(v.as_mut_ptr() as *mut __m256i, vel[i]);
                 let mut moon = moon.borrow_mut();
-                *moon.pos_mut() = new_pos;
+                *moon.pos_mut() = Vec3::from(p);
+                *moon.vel_mut() = Vec3::from(v);
             }
         }
     }
+}
 
-    #[derive(Copy, Clone, Debug)]
-    pub(crate) struct Moon {
-        pos: __m256i,
-        vel: __m256i,
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::utils;
 
-    impl PartialEq for Moon {
-        fn eq(&self, other: &Moon) -> bool {
-            self.pos() == other.pos() && self.vel() == other.vel()
+    #[test]
+    fn test_periods() {
+        let input = "<x=-1, y=0, z=2>\n<x=2, y=-10, z=-7>\n<x=4, y=-8, z=8>\n<x=3, y=5, z=-1>";
+        let system = parse_input(io::BufReader::new(input.as_bytes())).unwrap();
+        let mut moons = Moons::from_system(&system).unwrap();
+        let (energy, periods) = simulate(&mut moons, 10);
+        assert_eq!(energy, Some(179));
+        assert_eq!(periods, [18, 28, 44]);
+        for (axis, period) in periods.iter().enumerate() {
+            assert_eq!(system.axis_period(axis).unwrap(), *period);
         }
     }
 
-    impl Eq for Moon {}
+    /// Touches only in-memory data, so that it can also be run under Miri (e.g. with
+    /// `cargo +nightly miri test day12::tests::test_moons`) to check the moons are
+    /// built and read soundly.
+    #[test]
+    fn test_moons() {
+        let system = System::new(vec![
+            Body::new([1, 2, 3], [4, 5, 6]),
+            Body::new([-1, -2, -3], [0, 0, 0]),
+            Body::new([7, 8, 9], [-1, 0, 1]),
+            Body::new([0, 0, 0], [0, 0, 0]),
+        ]);
+        let mut moons = Moons::from_system(&system).unwrap();
+        assert_eq!(
+            moons.state(),
+            [
+                [(1, 4), (-1, 0), (7, -1), (0, 0)],
+                [(2, 5), (-2, 0), (8, 0), (0, 0)],
+                [(3, 6), (-3, 0), (9, 1), (0, 0)],
+            ]
+        );
+        assert_eq!(moons.energy(), 6 * 15 + 24 * 2);
 
-    impl Moon {
-        pub(crate) fn new<V, U>(pos: V, vel: U) -> Self
-        where
-            V: Into<Vec3<i64>>,
-            U: Into<Vec3<i64>>,
-        {
-            let pos = {
-                let pos = pos.into();
-                unsafe { _mm256_set_epi64x(pos.x(), pos.y(), pos.z(), 0) }
-            };
-            let vel = {
-                let vel = vel.into();
-                unsafe { _mm256_set_epi64x(vel.x(), vel.y(), vel.z(), 0) }
-            };
-            Self { pos, vel }
-        }
+        moons.step_scalar();
+        assert_eq!(moons.state()[0][0], (4, 3));
 
-        pub(crate) fn pos(&self) -> Vec3<i64> {
-            self.pos.into()
-        }
+        let system = System::new(vec![Body::new([0, 0, 0], [0, 0, 0])]);
+        assert!(Moons::from_system(&system).is_err());
+    }
 
-        pub(crate) fn pos_mut(&mut self) -> &mut __m256i {
-            &mut self.pos
+    #[test]
+    fn test_check_invariants() {
+        let input = "<x=-1, y=0, z=2>\n<x=2, y=-10, z=-7>\n<x=4, y=-8, z=8>\n<x=3, y=5, z=-1>";
+        let system = parse_input(io::BufReader::new(input.as_bytes())).unwrap();
+        let mut moons = Moons::from_system(&system).unwrap();
+        for _ in 0..100 {
+            moons.step();
+            moons.check_invariants().unwrap();
         }
 
-        pub(crate) fn vel(&self) -> Vec3<i64> {
-            self.vel.into()
+        let mut moon = moons[0].borrow_mut();
+        *moon.vel_mut() = *moon.vel() + Vec3::new(1, 0, -1);
+        drop(moon);
+        let e = moons.check_invariants().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "The moons' total velocity should stay [0, 0, 0], but it is [1, 0, -1]."
+        );
+
+        // Moons that start out moving keep their total velocity instead.
+        let system = System::new(vec![
+            Body::new([1, 2, 3], [4, 5, 6]),
+            Body::new([-1, -2, -3], [0, 0, 0]),
+            Body::new([7, 8, 9], [-1, 0, 1]),
+            Body::new([0, 0, 0], [0, 0, 0]),
+        ]);
+        let mut moons = Moons::from_system(&system).unwrap();
+        for _ in 0..100 {
+            moons.step();
+            moons.check_invariants().unwrap();
         }
+    }
 
-        pub(crate) fn vel_mut(&mut self) -> &mut __m256i {
-            &mut self.vel
+    #[test]
+    fn test_step_scalar_and_simd_agree() {
+        let input = "<x=-8, y=-10, z=0>\n<x=5, y=5, z=10>\n<x=2, y=-7, z=3>\n<x=9, y=-8, z=-3>";
+        let system = parse_input(io::BufReader::new(input.as_bytes())).unwrap();
+        let mut scalar = Moons::from_system(&system).unwrap();
+        let mut simd = scalar.clone();
+        let mut best = scalar.clone();
+        for _ in 0..100 {
+            scalar.step_scalar();
+            best.step();
+            if simd.step_simd().is_ok() {
+                assert_eq!(simd, scalar);
+            }
+            assert_eq!(best, scalar);
         }
+        assert_eq!(scalar.energy(), 1940);
+    }
 
-        pub(crate) fn state(&self) -> [(i64, i64); 3] {
-            let pos: Vec3<i64> = self.pos.into();
-            let vel: Vec3<i64> = self.vel.into();
-            [(pos.x(), vel.x()), (pos.y(), vel.y()), (pos.z(), vel.z())]
+    #[test]
+    fn test_12_any_cpu() {
+        let Some(reader) = utils::tests::data_file(12) else {
+            return;
+        };
+        let system = parse_input(reader).unwrap();
+        let expected = (Some(7722), [268296, 113028, 231614]);
+
+        let mut moons = Moons::from_system(&system).unwrap();
+        assert_eq!(
+            simulate_with(&mut moons, 1000, Moons::step_scalar),
+            expected
+        );
+
+        let mut moons = Moons::from_system(&system).unwrap();
+        if moons.clone().step_simd().is_ok() {
+            let step = |moons: &mut Moons| moons.step_simd().unwrap();
+            assert_eq!(simulate_with(&mut moons, 1000, step), expected);
         }
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use crate::utils;
 
     #[test]
     fn test_12() {
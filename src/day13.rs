@@ -1,34 +1,57 @@
-use std::cmp::Ordering;
+use std::cmp::{self, Ordering};
 use std::io;
 
-use crate::computer::{ComputerST, Queue, Rom, State};
+use crate::computer::{ComputerST, Hooks, Queue, Rom, State};
 use crate::error::Error;
 
+// Initial size of the display, which is big enough for the puzzle input. The display
+// grows if a program draws beyond it.
 const ROWS: usize = 26;
 const COLS: usize = 40;
 
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(reader, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(reader)?;
     let mut game = Game::new(&rom);
+    hooks.apply(&mut game.computer);
     game.run()?;
     Ok((game.nblocks.to_string(), game.score.to_string()))
 }
 
 pub struct Game {
     computer: ComputerST,
+    rom: Vec<i64>,
     display: Vec<u8>,
+    rows: usize,
+    cols: usize,
 
-    x: Option<i64>,
-    y: Option<i64>,
-    id: Option<i64>,
     score: i64,
     ball: i64,
+    ball_y: i64,
     paddle: i64,
     nblocks: usize,
     first: bool,
+    outcome: Option<GameOutcome>,
+}
+
+/// How a finished game ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum GameOutcome {
+    /// Every block was cleared.
+    Won,
+    /// The program halted with blocks still on the screen, i.e. the ball got past
+    /// the paddle.
+    Lost,
 }
 
 impl Game {
@@ -36,26 +59,48 @@ impl Game {
     where
         R: AsRef<[i64]>,
     {
-        let mut computer = ComputerST::new(rom.as_ref());
+        let rom = rom.as_ref().to_vec();
+        let mut computer = ComputerST::new(&rom);
         computer.write(0, 2);
         Self {
             computer,
+            rom,
             display: vec![0; COLS * ROWS],
+            rows: ROWS,
+            cols: COLS,
 
-            x: None,
-            y: None,
-            id: None,
             score: 0,
             ball: 0,
+            ball_y: 0,
             paddle: 0,
             nblocks: 0,
             first: true,
+            outcome: None,
         }
     }
 
+    /// Puts the game back to how it was when it was created, so it can be played
+    /// again. Memory and the display are reused rather than reallocated.
+    pub fn reset(&mut self) {
+        self.computer.load(&self.rom);
+        self.computer.write(0, 2);
+        self.display.clear();
+        self.display.resize(COLS * ROWS, 0);
+        self.rows = ROWS;
+        self.cols = COLS;
+
+        self.score = 0;
+        self.ball = 0;
+        self.ball_y = 0;
+        self.paddle = 0;
+        self.nblocks = 0;
+        self.first = true;
+        self.outcome = None;
+    }
+
     pub fn run(&mut self) -> Result<(), Error> {
         while let Some(next_move) = self.step()? {
-            self.computer.input_mut().enqueue(next_move);
+            self.computer.feed(&[next_move])?;
         }
         Ok(())
     }
@@ -63,7 +108,14 @@ impl Game {
     pub fn step(&mut self) -> Result<Option<i64>, Error> {
         loop {
             match self.computer.step()? {
-                State::Done => return Ok(None),
+                State::Done => {
+                    self.outcome = if self.blocks_remaining() == 0 {
+                        Some(GameOutcome::Won)
+                    } else {
+                        Some(GameOutcome::Lost)
+                    };
+                    return Ok(None);
+                }
                 State::NeedsInput => {
                     if self.first {
                         self.nblocks = bytecount::count(&self.display[..], 2);
@@ -76,63 +128,148 @@ impl Game {
                     }
                 }
                 State::HasOutput => {
-                    if self.x.is_none() {
-                        self.x = Some(self.computer.output_mut().dequeue().unwrap());
+                    // Tiles are drawn with three outputs: x, y, and tile id.
+                    let output = self.computer.output_mut();
+                    if output.len() < 3 {
                         continue;
                     }
-                    if self.y.is_none() {
-                        self.y = Some(self.computer.output_mut().dequeue().unwrap());
+                    let x = output.dequeue()?;
+                    let y = output.dequeue()?;
+                    let id = output.dequeue()?;
+                    if x == -1 && y == 0 {
+                        self.score = id;
                         continue;
                     }
-                    if self.id.is_none() {
-                        self.id = Some(self.computer.output_mut().dequeue().unwrap());
-                    }
-                    if self.x.is_some() && self.y.is_some() && self.id.is_some() {
-                        let x = self.x.unwrap();
-                        let y = self.y.unwrap();
-                        let id = self.id.unwrap();
-                        if x == -1 && y == 0 {
-                            self.score = id;
-                            self.x = None;
-                            self.y = None;
-                            self.id = None;
-                            continue;
-                        }
-                        match id {
-                            0 | 1 | 2 => (),
-                            3 => self.paddle = x,
-                            4 => self.ball = x,
-                            _ => bail!("Received invalid id: {}", id),
+                    match id {
+                        0 | 1 | 2 => (),
+                        3 => self.paddle = x,
+                        4 => {
+                            self.ball = x;
+                            self.ball_y = y;
                         }
-                        self.display[(y as usize) * COLS + x as usize] = id as u8;
-                        self.x = None;
-                        self.y = None;
-                        self.id = None;
+                        _ => bail!("Received invalid id: {}", id),
                     }
+                    self.draw(x, y, id as u8)?;
                 }
             };
         }
     }
 
-    pub const fn rows(&self) -> usize {
-        ROWS
+    pub fn rows(&self) -> usize {
+        self.rows
     }
 
-    pub const fn cols(&self) -> usize {
-        COLS
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn draw(&mut self, x: i64, y: i64, id: u8) -> Result<(), Error> {
+        if x < 0 || y < 0 {
+            bail!("Received tile at invalid position ({}, {})", x, y);
+        }
+        let (x, y) = (x as usize, y as usize);
+        if x >= self.cols || y >= self.rows {
+            self.resize(cmp::max(self.rows, y + 1), cmp::max(self.cols, x + 1));
+        }
+        self.display[y * self.cols + x] = id;
+        Ok(())
+    }
+
+    fn resize(&mut self, rows: usize, cols: usize) {
+        let mut display = vec![0; cols * rows];
+        for (y, row) in self.display.chunks(self.cols).enumerate() {
+            display[y * cols..y * cols + self.cols].copy_from_slice(row);
+        }
+        self.display = display;
+        self.rows = rows;
+        self.cols = cols;
     }
 
     pub fn display(&self) -> &[u8] {
         &self.display
     }
 
+    /// Renders the display as text, one line per row, drawing empty tiles as spaces,
+    /// walls as `+`, blocks as `#`, the paddle as `-`, and the ball as `o`.
+    pub fn display_string(&self) -> String {
+        let mut s = String::with_capacity(self.rows * (self.cols + 1));
+        for row in self.display.chunks(self.cols) {
+            s.extend(row.iter().map(|&id| match id {
+                1 => '+',
+                2 => '#',
+                3 => '-',
+                4 => 'o',
+                _ => ' ',
+            }));
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Like `display_string`, but with a header line showing the score.
+    pub fn render(&self) -> String {
+        format!("Score: {}\n{}", self.score, self.display_string())
+    }
+
+    /// Plays the game to the end, letting the paddle follow the ball, and draws every
+    /// frame to `out` as a terminal screen, waiting `frame` between frames.
+    #[cfg(feature = "tui")]
+    pub fn play_tui<W>(&mut self, mut out: W, frame: std::time::Duration) -> Result<(), Error>
+    where
+        W: io::Write,
+    {
+        // Clear the screen and move the cursor to the top left corner.
+        const CLEAR: &str = "\x1b[2J\x1b[H";
+        while let Some(next_move) = self.step()? {
+            write!(out, "{}{}", CLEAR, self.render())?;
+            out.flush()?;
+            std::thread::sleep(frame);
+            self.input(next_move);
+        }
+        write!(out, "{}{}", CLEAR, self.render())?;
+        out.flush()?;
+        Ok(())
+    }
+
     pub fn input(&mut self, val: i64) {
-        self.computer.input_mut().enqueue(val)
+        self.computer.input_mut().push_back(val)
+    }
+
+    /// Feeds the joystick position for a keyboard key, as named by a browser's
+    /// `KeyboardEvent.key`: left for `ArrowLeft`, right for `ArrowRight`, and neutral
+    /// for anything else.
+    pub fn input_key(&mut self, key: &str) {
+        let val = match key {
+            "ArrowLeft" => -1,
+            "ArrowRight" => 1,
+            _ => 0,
+        };
+        self.input(val)
     }
 
     pub fn score(&self) -> i64 {
         self.score
     }
+
+    /// Number of block tiles currently on the screen.
+    pub fn blocks_remaining(&self) -> usize {
+        bytecount::count(&self.display[..], 2)
+    }
+
+    /// The `(x, y)` position of the ball as last drawn.
+    pub fn ball_position(&self) -> (i64, i64) {
+        (self.ball, self.ball_y)
+    }
+
+    /// The x position of the paddle as last drawn.
+    pub fn paddle_x(&self) -> i64 {
+        self.paddle
+    }
+
+    /// How the game ended, or `None` if it is still being played.
+    pub fn outcome(&self) -> Option<GameOutcome> {
+        self.outcome
+    }
 }
 
 #[cfg(test)]
@@ -145,4 +282,132 @@ mod tests {
     fn test_13() {
         utils::tests::test_full_problem(13, run, "432", "22225")
     }
+
+    #[test]
+    fn test_outcome() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+        assert_eq!(game.outcome(), None);
+        game.run().unwrap();
+        assert_eq!(game.outcome(), Some(GameOutcome::Won));
+    }
+
+    #[test]
+    fn test_reset() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+        game.run().unwrap();
+        assert_eq!(game.outcome(), Some(GameOutcome::Won));
+
+        game.reset();
+        assert_eq!(game.outcome(), None);
+        assert_eq!(game.score(), 0);
+        assert_eq!(game.blocks_remaining(), 0);
+        assert!(game.display().iter().all(|&tile| tile == 0));
+
+        game.run().unwrap();
+        assert_eq!(game.outcome(), Some(GameOutcome::Won));
+        assert_eq!(game.score(), 22225);
+        assert_eq!(game.nblocks, 432);
+    }
+
+    #[test]
+    fn test_display_string() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+        game.step().unwrap();
+
+        let s = game.display_string();
+        assert_eq!(s.lines().count(), game.rows());
+        assert!(s.lines().all(|line| line.chars().count() == game.cols()));
+        assert_eq!(s.matches('#').count(), 432);
+        assert_eq!(s.matches('o').count(), 1);
+        assert_eq!(s.matches('-').count(), 1);
+        assert!(s.lines().next().unwrap().chars().all(|c| c == '+'));
+    }
+
+    #[test]
+    fn test_render() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+        game.run().unwrap();
+
+        let s = game.render();
+        assert_eq!(s.lines().count(), game.rows() + 1);
+        assert_eq!(s.lines().next(), Some("Score: 22225"));
+        assert!(s.ends_with(&game.display_string()));
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn test_play_tui() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+        let mut out = Vec::new();
+        game.play_tui(&mut out, std::time::Duration::default())
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with(&game.render()));
+        assert_eq!(game.outcome(), Some(GameOutcome::Won));
+    }
+
+    #[test]
+    fn test_input_key() {
+        let mut game = Game::new([99]);
+        game.input_key("ArrowLeft");
+        game.input_key("ArrowRight");
+        game.input_key(" ");
+        let input = game.computer.input_mut();
+        assert_eq!(input.dequeue().unwrap(), -1);
+        assert_eq!(input.dequeue().unwrap(), 1);
+        assert_eq!(input.dequeue().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_display_grows() {
+        // The leading instruction is a harmless multiply once address 0 is set to 2.
+        let rom = [
+            1, 0, 0, 20, 104, 50, 104, 30, 104, 1, 104, 3, 104, 2, 104, 2, 99,
+        ];
+        let mut game = Game::new(rom);
+        game.run().unwrap();
+        assert_eq!((game.rows(), game.cols()), (31, 51));
+        assert_eq!(game.display().len(), 31 * 51);
+        assert_eq!(game.display()[30 * 51 + 50], 1);
+        assert_eq!(game.display()[2 * 51 + 3], 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let Some(reader) = utils::tests::data_file(13) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let mut game = Game::new(&rom);
+
+        let next_move = game.step().unwrap().unwrap();
+        assert_eq!(game.blocks_remaining(), 432);
+        let (ball_x, _) = game.ball_position();
+        assert_eq!(next_move, (ball_x - game.paddle_x()).signum());
+        game.input(next_move);
+
+        game.run().unwrap();
+        assert_eq!(game.blocks_remaining(), 0);
+        assert_eq!(game.score(), 22225);
+    }
 }
@@ -2,8 +2,10 @@ use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::{TryFrom, TryInto};
 use std::io;
 
-use crate::computer::{ComputerST, Queue, Rom, State};
+use crate::computer::{ComputerST, Hooks, Queue, Rom, State};
 use crate::error::Error;
+use crate::utils::direction::Direction;
+use crate::utils::search;
 use crate::utils::Vec2;
 
 type Point = Vec2<i64>;
@@ -11,37 +13,63 @@ type Point = Vec2<i64>;
 const ORIGIN: Point = Point::new(0, 0);
 
 pub fn run<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(reader, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
 where
     R: io::BufRead,
 {
     let rom = Rom::from_reader(reader)?;
     let mut droid = Droid::new(rom);
+    droid.apply_hooks(hooks);
     let (oxygen, answer1) = droid.run()?;
     let answer2 = largest_layer(oxygen, &droid.graph);
 
     Ok((answer1.to_string(), answer2.to_string()))
 }
 
-fn largest_layer(start: Point, graph: &HashMap<Point, HashSet<Point>>) -> usize {
-    let mut queue = VecDeque::new();
-    let mut visited = HashSet::new();
-    let mut layers = HashMap::new();
-
-    queue.push_back(start);
-    visited.insert(start);
-    layers.insert(start, 0usize);
-
-    while let Some(parent) = queue.pop_front() {
-        visited.insert(parent);
-
-        for neighbor in graph.get(&parent).unwrap() {
-            if !visited.contains(neighbor) {
-                let layer = *layers.get(&parent).unwrap() + 1;
-                layers.insert(*neighbor, layer);
-                queue.push_back(*neighbor);
-            }
-        }
+/// Runs the droid until it has explored the entire maze.
+pub fn explore(rom: &Rom) -> Result<Maze, Error> {
+    explore_with_limit(rom, usize::MAX)
+}
+
+/// Like `explore`, but gives up with an error once the droid has visited more than
+/// `max_nodes` cells, rather than exploring a maze of any size.
+pub fn explore_with_limit(rom: &Rom, max_nodes: usize) -> Result<Maze, Error> {
+    let mut droid = Droid::new_with_limit(rom, max_nodes);
+    droid.run()?;
+    Ok(Maze(droid))
+}
+
+pub struct Maze(Droid);
+
+impl Maze {
+    /// Draws the explored maze with walls as `#`, open cells as `.`, the starting
+    /// point as `D`, the oxygen system as `O`, and unexplored cells as spaces.
+    pub fn render(&self) -> String {
+        self.0.render()
     }
+
+    /// The shortest sequence of `(x, y)` points leading from the starting point to
+    /// the oxygen system, excluding the starting point; i.e. one point per move.
+    pub fn path_to_oxygen(&self) -> Vec<(i64, i64)> {
+        self.0
+            .path_to_oxygen()
+            .into_iter()
+            .map(|point| (point.x(), point.y()))
+            .collect()
+    }
+}
+
+fn largest_layer(start: Point, graph: &HashMap<Point, HashSet<Point>>) -> usize {
+    let layers = search::bfs(start, |point| {
+        graph.get(point).into_iter().flatten().copied()
+    });
     *layers.values().max().unwrap()
 }
 
@@ -52,6 +80,9 @@ struct Droid {
     visited: HashSet<Point>,
     layers: HashMap<Point, usize>,
     graph: HashMap<Point, HashSet<Point>>,
+    walls: HashSet<Point>,
+    oxygen: Option<Point>,
+    max_nodes: Option<usize>,
 }
 
 impl Droid {
@@ -81,6 +112,29 @@ impl Droid {
             visited,
             layers,
             graph: HashMap::new(),
+            walls: HashSet::new(),
+            oxygen: None,
+            max_nodes: None,
+        }
+    }
+
+    /// Like `new`, but `run` gives up once it has visited more than `max_nodes`
+    /// cells, rather than exploring a maze of any size.
+    fn new_with_limit<R>(rom: R, max_nodes: usize) -> Self
+    where
+        R: AsRef<[i64]>,
+    {
+        Self {
+            max_nodes: Some(max_nodes),
+            ..Self::new(rom)
+        }
+    }
+
+    /// Installs `hooks` on the droid's computers; call it before `run`.
+    fn apply_hooks(&mut self, hooks: &Hooks) {
+        hooks.apply(&mut self.computer);
+        for (_, computer) in &mut self.queue {
+            hooks.apply(computer);
         }
     }
 
@@ -88,38 +142,40 @@ impl Droid {
         let mut output = None;
         while let Some((parent, computer)) = self.queue.pop_front() {
             self.visited.insert(parent);
+            if let Some(max_nodes) = self.max_nodes {
+                if self.visited.len() > max_nodes {
+                    bail!(
+                        "Explored more than {} cells without finishing the maze.",
+                        max_nodes
+                    );
+                }
+            }
             self.computer = computer;
-            for (point, command) in surrounding_points(parent).iter() {
-                if !self.visited.contains(point) {
-                    self.computer.input_mut().enqueue(*command as i64);
-                    if self.computer.step()? != State::HasOutput {
-                        bail!("Invalid computer program. Expected output.");
-                    };
-                    let response: Response = self.computer.output_mut().dequeue()?.try_into()?;
+            for (point, direction) in neighbors(parent) {
+                if !self.visited.contains(&point) {
+                    let response = self.send(direction)?;
                     match response {
-                        Response::Wall => {}
+                        Response::Wall => {
+                            self.walls.insert(point);
+                        }
                         Response::Move | Response::Oxygen => {
                             self.graph
                                 .entry(parent)
                                 .or_insert_with(HashSet::new)
-                                .insert(*point);
+                                .insert(point);
                             self.graph
-                                .entry(*point)
+                                .entry(point)
                                 .or_insert_with(HashSet::new)
                                 .insert(parent);
 
                             let layer = self.layers.get(&parent).unwrap() + 1;
-                            self.layers.insert(*point, layer);
-                            self.queue.push_back((*point, self.computer.clone()));
+                            self.layers.insert(point, layer);
+                            self.queue.push_back((point, self.computer.clone()));
                             if response == Response::Oxygen {
-                                output = Some((*point, layer));
+                                self.oxygen = Some(point);
+                                output = Some((point, layer));
                             }
-                            self.computer.input_mut().enqueue(command.opposite() as i64);
-                            if self.computer.step()? != State::HasOutput {
-                                bail!("Invalid computer program. Expected output.");
-                            };
-                            let response: Response =
-                                self.computer.output_mut().dequeue()?.try_into()?;
+                            let response = self.send(direction.opposite())?;
                             assert_eq!(response, Response::Move);
                         }
                     }
@@ -128,35 +184,88 @@ impl Droid {
         }
         output.ok_or_else(|| error!("Could not find oxygen"))
     }
-}
 
-fn surrounding_points(point: Point) -> [(Point, Command); 4] {
-    [
-        (Point::new(point.x(), point.y() + 1), Command::North),
-        (Point::new(point.x(), point.y() - 1), Command::South),
-        (Point::new(point.x() - 1, point.y()), Command::West),
-        (Point::new(point.x() + 1, point.y()), Command::East),
-    ]
+    /// Draws the explored maze with walls as `#`, open cells as `.`, the starting
+    /// point as `D`, the oxygen system as `O`, and unexplored cells as spaces.
+    fn render(&self) -> String {
+        let points = || self.graph.keys().chain(self.walls.iter());
+        let min_x = points().map(|p| p.x()).min().unwrap_or(0);
+        let max_x = points().map(|p| p.x()).max().unwrap_or(0);
+        let min_y = points().map(|p| p.y()).min().unwrap_or(0);
+        let max_y = points().map(|p| p.y()).max().unwrap_or(0);
+
+        let mut rows = Vec::new();
+        for y in (min_y..=max_y).rev() {
+            let row = (min_x..=max_x)
+                .map(|x| {
+                    let point = Point::new(x, y);
+                    if point == ORIGIN {
+                        'D'
+                    } else if Some(point) == self.oxygen {
+                        'O'
+                    } else if self.walls.contains(&point) {
+                        '#'
+                    } else if self.graph.contains_key(&point) {
+                        '.'
+                    } else {
+                        ' '
+                    }
+                })
+                .collect::<String>();
+            rows.push(row);
+        }
+        rows.join("\n")
+    }
+
+    /// The shortest sequence of points leading from the starting point to the oxygen
+    /// system, excluding the starting point; i.e. one point per move. Empty if the
+    /// oxygen system hasn't been found.
+    fn path_to_oxygen(&self) -> Vec<Point> {
+        let oxygen = match self.oxygen {
+            Some(oxygen) => oxygen,
+            None => return Vec::new(),
+        };
+
+        let graph = &self.graph;
+        let mut path = search::shortest_path(ORIGIN, &oxygen, |point| {
+            graph.get(point).into_iter().flatten().copied()
+        })
+        .unwrap_or_default();
+        if !path.is_empty() {
+            path.remove(0);
+        }
+        path
+    }
+
+    fn send(&mut self, direction: Direction) -> Result<Response, Error> {
+        self.computer.input_mut().enqueue(command(direction))?;
+        match self.computer.step()? {
+            State::HasOutput => self.computer.output_mut().dequeue()?.try_into(),
+            state => bail!(
+                "Invalid computer program. Expected {} but got {}.",
+                State::HasOutput,
+                state
+            ),
+        }
+    }
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-#[repr(i64)]
-enum Command {
-    North = 1,
-    South = 2,
-    West = 3,
-    East = 4,
+/// The four points adjacent to `point`, in north, south, east, west order, along
+/// with the direction to move to reach each one.
+fn neighbors(point: Point) -> impl Iterator<Item = (Point, Direction)> {
+    use self::Direction::*;
+    // In the same order as `neighbors4`.
+    IntoIterator::into_iter(point.neighbors4())
+        .zip(IntoIterator::into_iter([North, South, East, West]))
 }
 
-impl Command {
-    fn opposite(self) -> Command {
-        use self::Command::*;
-        match self {
-            North => South,
-            South => North,
-            West => East,
-            East => West,
-        }
+/// Encodes a direction as the movement command understood by the droid.
+fn command(direction: Direction) -> i64 {
+    match direction {
+        Direction::North => 1,
+        Direction::South => 2,
+        Direction::West => 3,
+        Direction::East => 4,
     }
 }
 
@@ -189,6 +298,78 @@ mod tests {
 
     use crate::utils;
 
+    #[test]
+    fn test_neighbors() {
+        let actual = neighbors(ORIGIN)
+            .map(|(point, direction)| (point, command(direction)))
+            .collect::<Vec<_>>();
+        let expected = vec![
+            (Point::new(0, 1), 1),
+            (Point::new(0, -1), 2),
+            (Point::new(1, 0), 4),
+            (Point::new(-1, 0), 3),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_path_to_oxygen() {
+        let Some(reader) = utils::tests::data_file(15) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+        let maze = explore(&rom).unwrap();
+        let droid = &maze.0;
+
+        let path = maze.path_to_oxygen();
+        assert_eq!(path.len(), 248);
+        let oxygen = droid.oxygen.unwrap();
+        assert_eq!(path.last(), Some(&(oxygen.x(), oxygen.y())));
+        let path = path
+            .into_iter()
+            .map(|(x, y)| Point::new(x, y))
+            .collect::<Vec<_>>();
+        for (a, b) in Some(ORIGIN).iter().chain(path.iter()).zip(path.iter()) {
+            assert!(droid.graph[a].contains(b));
+        }
+
+        let map = maze.render();
+        assert_eq!(map.matches('D').count(), 1);
+        assert_eq!(map.matches('O').count(), 1);
+        assert_eq!(map.matches('.').count(), droid.graph.len() - 2);
+        assert_eq!(map.matches('#').count(), droid.walls.len());
+    }
+
+    #[test]
+    fn test_new_with_limit() {
+        let Some(reader) = utils::tests::data_file(15) else {
+            return;
+        };
+        let rom = Rom::from_reader(reader).unwrap();
+
+        let mut droid = Droid::new_with_limit(&rom, 10);
+        let e = droid.run().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Explored more than 10 cells without finishing the maze."
+        );
+        assert_eq!(droid.visited.len(), 11);
+
+        let mut droid = Droid::new_with_limit(&rom, 10_000);
+        assert_eq!(droid.run().unwrap().1, 248);
+
+        assert!(explore_with_limit(&rom, 10).is_err());
+        let maze = explore_with_limit(&rom, 10_000).unwrap();
+        assert_eq!(maze.path_to_oxygen().len(), 248);
+    }
+
+    #[test]
+    fn test_unexpected_state() {
+        let mut droid = Droid::new([99]);
+        let e = droid.run().unwrap_err();
+        assert!(e.to_string().contains("Expected HasOutput but got Done"));
+    }
+
     #[test]
     fn test_15() {
         utils::tests::test_full_problem(15, run, "248", "382");
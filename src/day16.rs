@@ -0,0 +1,134 @@
+use std::io;
+
+use crate::error::Error;
+
+const BASE_PATTERN: [i64; 4] = [0, 1, 0, -1];
+const NPHASES: usize = 100;
+const NREPEATS: usize = 10_000;
+
+pub fn run<R>(mut reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    let mut buf = String::new();
+    reader.read_to_string(&mut buf)?;
+    let signal = parse_signal(buf.trim())?;
+
+    // Part 1
+    let output = phases(&signal, NPHASES)
+        .last()
+        .unwrap_or_else(|| signal.clone());
+    let answer1 = to_string(&output[..8.min(output.len())]);
+
+    // Part 2
+    let answer2 = decode(&signal)?;
+
+    Ok((answer1, answer2))
+}
+
+/// Yields the signal after each of the first `count` phases of the FFT.
+pub fn phases(signal: &[i8], count: usize) -> impl Iterator<Item = Vec<i8>> {
+    let mut current = signal.to_vec();
+    (0..count).map(move |_| {
+        current = phase(&current);
+        current.clone()
+    })
+}
+
+fn phase(signal: &[i8]) -> Vec<i8> {
+    (0..signal.len())
+        .map(|i| {
+            let sum = signal
+                .iter()
+                .enumerate()
+                .map(|(j, &digit)| digit as i64 * BASE_PATTERN[((j + 1) / (i + 1)) % 4])
+                .sum::<i64>();
+            (sum.abs() % 10) as i8
+        })
+        .collect()
+}
+
+/// Finds the eight digit message in the real signal (the input repeated 10,000
+/// times), at the offset given by the input's first seven digits.
+///
+/// This relies on the offset being in the second half of the real signal, where
+/// the pattern for every digit is zeros followed by ones; so after each phase a
+/// digit is just the sum of itself and every digit after it.
+fn decode(signal: &[i8]) -> Result<String, Error> {
+    if signal.len() < 7 {
+        bail!("Signal must have at least 7 digits to contain a message offset.");
+    }
+    let offset = signal[..7]
+        .iter()
+        .fold(0usize, |acc, &digit| acc * 10 + digit as usize);
+    let len = signal.len() * NREPEATS;
+    if offset < len / 2 || offset + 8 > len {
+        bail!(
+            "Message offset {} must be in the second half of a signal of length {}.",
+            offset,
+            len
+        );
+    }
+
+    let mut tail = (offset..len)
+        .map(|i| signal[i % signal.len()])
+        .collect::<Vec<_>>();
+    for _ in 0..NPHASES {
+        for i in (0..tail.len() - 1).rev() {
+            tail[i] = (tail[i] + tail[i + 1]) % 10;
+        }
+    }
+    Ok(to_string(&tail[..8]))
+}
+
+fn parse_signal(s: &str) -> Result<Vec<i8>, Error> {
+    s.chars()
+        .map(|c| match c.to_digit(10) {
+            Some(digit) => Ok(digit as i8),
+            None => bail!("Invalid input. {:?} is not a digit.", c),
+        })
+        .collect()
+}
+
+fn to_string(digits: &[i8]) -> String {
+    digits.iter().map(|digit| digit.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_16_phases() {
+        let signal = parse_signal("12345678").unwrap();
+        let actual = phases(&signal, 4)
+            .map(|phase| to_string(&phase))
+            .collect::<Vec<_>>();
+        assert_eq!(actual, vec!["48226158", "34040438", "03415518", "01029498"]);
+    }
+
+    #[test]
+    fn test_16() {
+        let test_cases = &[
+            ("80871224585914546619083218645595", "24176176"),
+            ("19617804207202209144916044189917", "73745418"),
+            ("69317163492948606335995924319873", "52432133"),
+        ];
+        for (input, expected) in test_cases {
+            let signal = parse_signal(input).unwrap();
+            let output = phases(&signal, NPHASES).last().unwrap();
+            assert_eq!(&to_string(&output[..8]), expected);
+        }
+
+        let test_cases = &[
+            ("03036732577212944063491565474664", "84462026"),
+            ("02935109699940807407585447034323", "78725270"),
+            ("03081770884921959731165446850517", "53553731"),
+        ];
+        for (input, expected) in test_cases {
+            let reader = io::BufReader::new(input.as_bytes());
+            let (_, actual) = run(reader).unwrap();
+            assert_eq!(&actual, expected);
+        }
+    }
+}
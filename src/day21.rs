@@ -0,0 +1,230 @@
+use std::fmt;
+use std::io;
+
+use crate::computer::{ComputerST, Hooks, Rom};
+use crate::error::Error;
+
+/// The most instructions the springdroid's memory can hold.
+const MAX_INSTRUCTIONS: usize = 15;
+
+pub fn run<R>(reader: R) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    run_with_hooks(reader, &Hooks::default())
+}
+
+/// Like `run`, but installs `hooks` on every computer it runs.
+pub fn run_with_hooks<R>(reader: R, hooks: &Hooks) -> Result<(String, String), Error>
+where
+    R: io::BufRead,
+{
+    use Instruction::*;
+    use Register::*;
+
+    let rom = Rom::from_reader(reader)?;
+
+    // Part 1
+    // The droid jumps four tiles, so it should jump whenever there's a hole in any of
+    // the next three tiles, as long as there's ground four tiles ahead to land on:
+    // J = (!A || !B || !C) && D.
+    let script = [
+        Not(A, J),
+        Not(B, T),
+        Or(T, J),
+        Not(C, T),
+        Or(T, J),
+        And(D, J),
+    ];
+    let answer1 = survey_with_hooks(&rom, &script, Mode::Walk, hooks)?;
+
+    // Part 2
+    // As above, but only if, after landing on D, the droid can either take another
+    // step (E) or jump again straight away (H): J = (!A || !B || !C) && D && (E || H).
+    let script = [
+        Not(A, J),
+        Not(B, T),
+        Or(T, J),
+        Not(C, T),
+        Or(T, J),
+        And(D, J),
+        Not(E, T),
+        Not(T, T),
+        Or(H, T),
+        And(T, J),
+    ];
+    let answer2 = survey_with_hooks(&rom, &script, Mode::Run, hooks)?;
+
+    Ok((answer1.to_string(), answer2.to_string()))
+}
+
+/// Runs the springdroid with `script` and returns the hull damage it reports. If
+/// the droid falls into space instead, the error shows its last moments.
+pub fn survey(rom: &Rom, script: &[Instruction], mode: Mode) -> Result<i64, Error> {
+    survey_with_hooks(rom, script, mode, &Hooks::default())
+}
+
+fn survey_with_hooks(
+    rom: &Rom,
+    script: &[Instruction],
+    mode: Mode,
+    hooks: &Hooks,
+) -> Result<i64, Error> {
+    let mut computer = ComputerST::new(rom);
+    hooks.apply(&mut computer);
+    computer.input_from_ascii_art(&assemble(script, mode)?)?;
+    computer.run()?;
+
+    let outputs = computer.collect_outputs();
+    match outputs.last() {
+        Some(&damage) if damage > 127 => Ok(damage),
+        _ => {
+            let rendered = outputs.iter().map(|&b| b as u8 as char).collect::<String>();
+            bail!("The springdroid fell into space:\n{}", rendered.trim_end())
+        }
+    }
+}
+
+/// Translates `script` into the springscript text the springdroid reads, ending
+/// with `WALK` or `RUN`.
+pub fn assemble(script: &[Instruction], mode: Mode) -> Result<String, Error> {
+    if script.len() > MAX_INSTRUCTIONS {
+        bail!(
+            "Springscript can have at most {} instructions, but got {}.",
+            MAX_INSTRUCTIONS,
+            script.len()
+        );
+    }
+
+    let mut s = String::new();
+    for instruction in script {
+        let (x, y) = instruction.registers();
+        if !y.is_writable() {
+            bail!("Cannot write to register {} in {}.", y, instruction);
+        }
+        if mode == Mode::Walk && x.is_long_range_sensor() {
+            bail!(
+                "Register {} can only be read when running, in {}.",
+                x,
+                instruction
+            );
+        }
+        s.push_str(&format!("{}\n", instruction));
+    }
+    s.push_str(match mode {
+        Mode::Walk => "WALK\n",
+        Mode::Run => "RUN\n",
+    });
+    Ok(s)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+/// A springscript instruction, which reads its first register and writes its second.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    And(Register, Register),
+    Or(Register, Register),
+    Not(Register, Register),
+}
+
+impl Instruction {
+    fn registers(&self) -> (Register, Register) {
+        match *self {
+            Self::And(x, y) | Self::Or(x, y) | Self::Not(x, y) => (x, y),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (x, y) = self.registers();
+        let op = match self {
+            Self::And(..) => "AND",
+            Self::Or(..) => "OR",
+            Self::Not(..) => "NOT",
+        };
+        write!(f, "{} {} {}", op, x, y)
+    }
+}
+
+/// The springdroid's registers: `A` through `I` sense whether there's ground one
+/// through nine tiles ahead, `T` is scratch space, and `J` decides whether to jump.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Register {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    T,
+    J,
+}
+
+impl Register {
+    fn is_writable(self) -> bool {
+        matches!(self, Self::T | Self::J)
+    }
+
+    /// Sensors that only exist in `RUN` mode.
+    fn is_long_range_sensor(self) -> bool {
+        matches!(self, Self::E | Self::F | Self::G | Self::H | Self::I)
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Instruction::*;
+    use Register::*;
+
+    #[test]
+    fn test_assemble() {
+        let script = [Not(A, J), Not(B, T), And(T, J), Or(D, J)];
+        assert_eq!(
+            assemble(&script, Mode::Walk).unwrap(),
+            "NOT A J\nNOT B T\nAND T J\nOR D J\nWALK\n"
+        );
+        assert_eq!(assemble(&[], Mode::Run).unwrap(), "RUN\n");
+
+        assert!(assemble(&[Not(J, A)], Mode::Walk).is_err());
+        assert!(assemble(&[Or(H, J)], Mode::Walk).is_err());
+        assert!(assemble(&[Or(H, J)], Mode::Run).is_ok());
+
+        let script = [Not(A, J); MAX_INSTRUCTIONS];
+        assert!(assemble(&script, Mode::Walk).is_ok());
+        let script = [Not(A, J); MAX_INSTRUCTIONS + 1];
+        let e = assemble(&script, Mode::Walk).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "Springscript can have at most 15 instructions, but got 16."
+        );
+    }
+
+    #[test]
+    fn test_survey() {
+        // Stand-ins for the springdroid, which ignore the script: one reports damage
+        // and the other draws a "#" and a newline before falling.
+        let rom = Rom::from_reader("104,19352638,99".as_bytes()).unwrap();
+        assert_eq!(survey(&rom, &[Not(A, J)], Mode::Walk).unwrap(), 19352638);
+
+        let rom = Rom::from_reader("104,35,104,10,99".as_bytes()).unwrap();
+        let e = survey(&rom, &[Not(A, J)], Mode::Walk).unwrap_err();
+        assert_eq!(e.to_string(), "The springdroid fell into space:\n#");
+    }
+}
@@ -5,6 +5,9 @@ mod computer;
 pub mod day01;
 pub mod day02;
 pub mod day03;
+/// An independent solution to day 3, which walks every point of both wires.
+#[path = "alternatives/day03.rs"]
+pub mod day03_v2;
 pub mod day04;
 pub mod day05;
 pub mod day06;
@@ -17,22 +20,110 @@ pub mod day12;
 pub mod day13;
 pub mod day14;
 pub mod day15;
+pub mod day16;
+pub mod day21;
+pub mod nbody;
 mod utils;
 
-pub use self::computer::Rom;
+pub use self::computer::{
+    assemble, disassemble, repl, Channel, Computer, ComputerMT, ComputerST, Hooks, Profile, Queue,
+    Rom, State,
+};
 pub use self::error::Error;
 pub use self::reader::Reader;
-pub use day13::Game;
+pub use day13::{Game, GameOutcome};
+
+type PlainSolver = fn(Reader<'static>) -> Result<(String, String), Error>;
+type IntcodeSolver = fn(Reader<'static>, &Hooks) -> Result<(String, String), Error>;
+
+#[derive(Copy, Clone)]
+enum Solver {
+    Plain(PlainSolver),
+    /// A day that runs intcode computers, which it installs `Hooks` on.
+    Intcode(IntcodeSolver),
+}
+
+const SOLVERS: [Option<Solver>; 21] = [
+    Some(Solver::Plain(day01::run)),
+    Some(Solver::Intcode(day02::run_with_hooks)),
+    Some(Solver::Plain(day03::run)),
+    Some(Solver::Plain(day04::run)),
+    Some(Solver::Intcode(day05::run_with_hooks)),
+    Some(Solver::Plain(day06::run)),
+    Some(Solver::Intcode(day07::run_with_hooks)),
+    Some(Solver::Plain(day08::run)),
+    Some(Solver::Intcode(day09::run_with_hooks)),
+    Some(Solver::Plain(day10::run)),
+    Some(Solver::Intcode(day11::run_with_hooks)),
+    Some(Solver::Plain(day12::run)),
+    Some(Solver::Intcode(day13::run_with_hooks)),
+    Some(Solver::Plain(day14::run)),
+    Some(Solver::Intcode(day15::run_with_hooks)),
+    Some(Solver::Plain(day16::run)),
+    None,
+    None,
+    None,
+    None,
+    Some(Solver::Intcode(day21::run_with_hooks)),
+];
+
+/// The last day with a solution. Some of the days before it may not have one yet.
+pub const NDAYS: usize = SOLVERS.len();
+
+/// Solves both parts of `day` for the puzzle input read from `input`.
+pub fn solve_day(day: usize, input: Reader<'static>) -> Result<(String, String), Error> {
+    solve_day_with_hooks(day, input, &Hooks::default())
+}
+
+/// Like `solve_day`, but installs `hooks` on every intcode computer the day runs.
+pub fn solve_day_with_hooks(
+    day: usize,
+    input: Reader<'static>,
+    hooks: &Hooks,
+) -> Result<(String, String), Error> {
+    match day {
+        n if n > 0 && n < 26 => match SOLVERS.get(n - 1).copied().flatten() {
+            Some(Solver::Plain(solver)) => solver(input),
+            Some(Solver::Intcode(solver)) => solver(input, hooks),
+            None => bail!("Day {} is not yet implemented.", n),
+        },
+        _ => bail!("Day must be between 1 and 25, inclusive."),
+    }
+}
+
+/// Solves both parts of `day` for the puzzle input `input`.
+pub fn run_day(day: usize, input: &str) -> Result<(String, String), Error> {
+    solve_day(day, Reader::from_bytes(input))
+}
 
 mod error {
     use std::fmt;
     use std::io;
+    use std::time::Duration;
 
     #[derive(Debug)]
     pub enum Error {
+        AddressingMode(u64),
+        /// An error from a computer with `Computer::set_dump_on_error` turned on,
+        /// along with its `Computer::core_dump` from when it happened.
+        CoreDump {
+            dump: String,
+            source: Box<Error>,
+        },
         Custom(String),
         Io(io::Error),
+        /// An error from a computer given a name with `Computer::set_label`.
+        Labeled {
+            label: String,
+            source: Box<Error>,
+        },
+        NeedsInput,
+        NegativePointer(i64),
+        Opcode(u64),
         ParseInt(std::num::ParseIntError),
+        Timeout {
+            duration: Duration,
+        },
     }
 
     impl From<io::Error> for Error {
@@ -50,14 +141,58 @@ mod error {
     impl fmt::Display for Error {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match self {
+                Self::AddressingMode(n) => write!(f, "Unrecognized addressing mode {}", n),
+                Self::CoreDump { dump, source } => write!(f, "{}\n{}", source, dump),
                 Self::Custom(s) => write!(f, "{}", s),
                 Self::Io(e) => write!(f, "{}", e),
+                Self::Labeled { label, source } => write!(f, "{}: {}", label, source),
+                Self::NeedsInput => write!(f, "Needs input."),
+                Self::NegativePointer(n) => write!(
+                    f,
+                    "Encountered negative pointer {}, which is not allowed.",
+                    n
+                ),
+                Self::Opcode(n) => write!(f, "Unrecognized opcode {}", n),
                 Self::ParseInt(e) => write!(f, "{}", e),
+                Self::Timeout { duration } => write!(
+                    f,
+                    "Attempted to pop value off channel, but timed out after {:?}.",
+                    duration
+                ),
             }
         }
     }
 
-    impl std::error::Error for Error {}
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                Self::CoreDump { source, .. } => Some(&**source),
+                Self::Io(e) => Some(e),
+                Self::Labeled { source, .. } => Some(&**source),
+                Self::ParseInt(e) => Some(e),
+                _ => None,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::error::Error as _;
+        use std::fs;
+
+        use super::*;
+
+        #[test]
+        fn test_source() {
+            let e = Error::from(fs::File::open("data/does-not-exist.txt").unwrap_err());
+            assert!(e.source().is_some());
+
+            let e = Error::from("abc".parse::<u64>().unwrap_err());
+            assert!(e.source().is_some());
+
+            assert!(error!("custom").source().is_none());
+        }
+    }
 }
 
 mod reader {
@@ -65,13 +200,25 @@ mod reader {
     use std::io;
 
     pub enum Reader<'a> {
+        Bytes(io::Cursor<Vec<u8>>),
         File(io::BufReader<fs::File>),
         Stdin(io::StdinLock<'a>),
     }
 
+    impl Reader<'static> {
+        /// Reads from an in-memory copy of `bytes`.
+        pub fn from_bytes<B>(bytes: B) -> Self
+        where
+            B: Into<Vec<u8>>,
+        {
+            Self::Bytes(io::Cursor::new(bytes.into()))
+        }
+    }
+
     impl<'a> io::Read for Reader<'a> {
         fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
             match self {
+                Self::Bytes(cursor) => cursor.read(buf),
                 Self::File(reader) => reader.read(buf),
                 Self::Stdin(guard) => guard.read(buf),
             }
@@ -81,6 +228,7 @@ mod reader {
     impl<'a> io::BufRead for Reader<'a> {
         fn fill_buf(&mut self) -> io::Result<&[u8]> {
             match self {
+                Self::Bytes(cursor) => cursor.fill_buf(),
                 Self::File(reader) => reader.fill_buf(),
                 Self::Stdin(guard) => guard.fill_buf(),
             }
@@ -88,9 +236,26 @@ mod reader {
 
         fn consume(&mut self, amt: usize) {
             match self {
+                Self::Bytes(cursor) => cursor.consume(amt),
                 Self::File(reader) => reader.consume(amt),
                 Self::Stdin(guard) => guard.consume(amt),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_day() {
+        let (answer1, answer2) = run_day(1, "12\n14").unwrap();
+        assert_eq!((answer1.as_str(), answer2.as_str()), ("4", "4"));
+
+        assert!(run_day(0, "").is_err());
+        assert!(run_day(17, "").is_err());
+        assert!(run_day(NDAYS + 1, "").is_err());
+        assert!(run_day(26, "").is_err());
+    }
+}
@@ -1,19 +1,100 @@
 use std::fs;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use structopt::StructOpt;
+use clap::{Args, Parser, Subcommand};
 
-use aoc2019::{self, bail, Error, Reader};
+use aoc2019::{self, bail, solve_day, Error, Hooks, Reader, Rom};
 
-#[derive(Debug, StructOpt)]
-struct Opt {
+const BENCHMARK_RUNS: usize = 3;
+
+/// Stands in for answers spanning several lines (e.g. rendered letters) in `--summary`.
+const MULTILINE_MARKER: &str = "<multi-line>";
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// With no subcommand, solves a single day as `run` does
+    #[command(flatten)]
+    run: Option<RunArgs>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Solve a single day (the default when no subcommand is given)
+    Run(RunArgs),
+
+    /// Run every implemented day using its input file in the data directory
+    All(AllArgs),
+
+    /// Time every implemented day and print the median of several runs as a markdown table
+    Bench,
+
+    /// Print a disassembly of an intcode program
+    Disasm(DisasmArgs),
+
+    /// Run an intcode program interactively, reading its input from stdin
+    Repl(ReplArgs),
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
     /// Day
     day: usize,
 
     /// Optional path to input file; if not supplied will read from stdin
     input: Option<PathBuf>,
+
+    /// Optional puzzle input given inline instead of as a file or on stdin
+    #[arg(long, conflicts_with = "input")]
+    program: Option<String>,
+
+    /// Write a disassembly of every executed intcode instruction to this file
+    #[arg(long)]
+    trace_file: Option<PathBuf>,
+
+    /// Solve the day this many times and print the total and average time taken to
+    /// stderr
+    #[arg(long, value_name = "N", conflicts_with = "trace_file")]
+    #[arg(value_parser = clap::value_parser!(u32).range(1..))]
+    repeat: Option<u32>,
+
+    /// Print how long the day took to stderr (or as an "elapsed_ms" field with --json)
+    #[arg(long)]
+    time: bool,
+
+    /// Print the answers as a single JSON object instead of one per line
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Debug, Args)]
+struct AllArgs {
+    /// Print a compact, aligned table of the answers instead of one line per day
+    #[arg(long)]
+    summary: bool,
+
+    /// Print how long each day took to stderr
+    #[arg(long, conflicts_with = "summary")]
+    time: bool,
+}
+
+#[derive(Debug, Args)]
+struct DisasmArgs {
+    /// Optional path to the program; if not supplied will read from stdin
+    input: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ReplArgs {
+    /// Path to the program
+    input: PathBuf,
 }
 
 fn main() {
@@ -29,44 +110,526 @@ fn main() {
 }
 
 fn run() -> Result<(), Error> {
-    let opt = Opt::from_args();
+    let cli = Cli::parse();
 
-    let stdin = io::stdin();
+    let command = match (cli.command, cli.run) {
+        (Some(command), _) => command,
+        (None, Some(args)) => Command::Run(args),
+        (None, None) => bail!("Either a day or a subcommand is required. See --help."),
+    };
 
-    let input = match opt.input {
-        Some(path) => {
-            let file = fs::File::open(path)?;
-            let reader = io::BufReader::new(file);
-            Reader::File(reader)
+    match command {
+        Command::Run(args) => run_day(args),
+        Command::All(args) if args.summary => {
+            let stdout = io::stdout();
+            summary(1..=aoc2019::NDAYS, stdout.lock())
+        }
+        Command::All(args) => run_all(args.time),
+        Command::Bench => {
+            let stdout = io::stdout();
+            benchmark_all(1..=aoc2019::NDAYS, stdout.lock())
         }
-        None => {
-            let guard = stdin.lock();
-            Reader::Stdin(guard)
+        Command::Disasm(args) => {
+            let rom: Rom = match args.input {
+                Some(path) => Rom::from_reader(open(path)?)?,
+                None => Rom::from_reader(io::stdin().lock())?,
+            };
+            print!("{}", aoc2019::disassemble(&rom));
+            Ok(())
         }
+        Command::Repl(args) => {
+            let rom = Rom::from_reader(open(args.input)?)?;
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            aoc2019::repl(&rom, stdin.lock(), stdout.lock())
+        }
+    }
+}
+
+fn run_day(args: RunArgs) -> Result<(), Error> {
+    let day = args.day;
+    let input = match (args.program, args.input) {
+        (Some(_), Some(_)) => bail!("Cannot supply both --program and an input file."),
+        (Some(program), None) => Reader::from_bytes(program),
+        (None, Some(path)) => open(path)?,
+        (None, None) => Reader::Stdin(io::stdin().lock()),
     };
 
-    let (answer1, answer2) = match opt.day {
-        1 => aoc2019::day01::run(input)?,
-        2 => aoc2019::day02::run(input)?,
-        3 => aoc2019::day03::run(input)?,
-        4 => aoc2019::day04::run(input)?,
-        5 => aoc2019::day05::run(input)?,
-        6 => aoc2019::day06::run(input)?,
-        7 => aoc2019::day07::run(input)?,
-        8 => aoc2019::day08::run(input)?,
-        9 => aoc2019::day09::run(input)?,
-        10 => aoc2019::day10::run(input)?,
-        11 => aoc2019::day11::run(input)?,
-        12 => aoc2019::day12::run(input)?,
-        13 => aoc2019::day13::run(input)?,
-        14 => aoc2019::day14::run(input)?,
-        15 => aoc2019::day15::run(input)?,
-        n if n > 0 && n < 26 => bail!("Day {} is not yet implemented.", n),
-        _ => bail!("Day must be between 1 and 25, inclusive."),
+    let start = Instant::now();
+    let (answer1, answer2) = match (args.trace_file, args.repeat) {
+        (Some(path), _) => solve_day_traced(day, input, path)?,
+        (None, Some(n)) => solve_day_repeatedly(day, input, n)?,
+        (None, None) => solve_day(day, input)?,
     };
+    let elapsed = start.elapsed();
+
+    if args.json {
+        let elapsed = if args.time { Some(elapsed) } else { None };
+        let stdout = io::stdout();
+        write_json(stdout.lock(), day, &answer1, &answer2, elapsed)?;
+        return Ok(());
+    }
 
     println!("{}", answer1);
     println!("{}", answer2);
 
+    if args.time {
+        eprintln!("day {} took {:.2?}", day, elapsed);
+    }
+
+    Ok(())
+}
+
+/// Writes a day's answers as a JSON object on one line, e.g.
+/// `{"day":1,"part1":"...","part2":"..."}`, plus an `"elapsed_ms"` field if
+/// `elapsed` is given.
+fn write_json<W>(
+    mut out: W,
+    day: usize,
+    answer1: &str,
+    answer2: &str,
+    elapsed: Option<Duration>,
+) -> Result<(), Error>
+where
+    W: Write,
+{
+    write!(
+        out,
+        "{{\"day\":{},\"part1\":{},\"part2\":{}",
+        day,
+        json_string(answer1),
+        json_string(answer2)
+    )?;
+    if let Some(elapsed) = elapsed {
+        write!(out, ",\"elapsed_ms\":{:.3}", elapsed.as_secs_f64() * 1000.0)?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+/// Quotes and escapes `s` as a JSON string.
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn run_all(time: bool) -> Result<(), Error> {
+    let mut nfailed = 0;
+    for day in 1..=aoc2019::NDAYS {
+        let path = data_path(day);
+        if !path.exists() {
+            continue;
+        }
+
+        let start = Instant::now();
+        match open(&path).and_then(|input| solve_day(day, input)) {
+            Ok((answer1, answer2)) => println!("day {:02}: {} / {}", day, answer1, answer2),
+            Err(e) => {
+                eprintln!("day {:02}: {}", day, e);
+                nfailed += 1;
+            }
+        }
+        let elapsed = start.elapsed();
+
+        if time {
+            eprintln!("day {} took {:.2?}", day, elapsed);
+        }
+    }
+
+    if nfailed > 0 {
+        bail!("{} day(s) failed.", nfailed);
+    }
+
+    Ok(())
+}
+
+fn benchmark_all<I, W>(days: I, mut out: W) -> Result<(), Error>
+where
+    I: IntoIterator<Item = usize>,
+    W: Write,
+{
+    writeln!(out, "| Day | Median (ms) |")?;
+    writeln!(out, "| --- | ----------: |")?;
+    for day in days {
+        let path = data_path(day);
+        if !path.exists() {
+            continue;
+        }
+
+        let bytes = fs::read(path)?;
+        let mut durations = Vec::with_capacity(BENCHMARK_RUNS);
+        for _ in 0..BENCHMARK_RUNS {
+            let input = Reader::from_bytes(bytes.clone());
+            let start = Instant::now();
+            solve_day(day, input)?;
+            durations.push(start.elapsed());
+        }
+        durations.sort();
+        let median = durations[BENCHMARK_RUNS / 2];
+
+        writeln!(out, "| {:02} | {:.3} |", day, median.as_secs_f64() * 1000.0)?;
+    }
+    Ok(())
+}
+
+fn summary<I, W>(days: I, mut out: W) -> Result<(), Error>
+where
+    I: IntoIterator<Item = usize>,
+    W: Write,
+{
+    let compact = |answer: String| {
+        let answer = answer.trim();
+        if answer.contains('\n') {
+            MULTILINE_MARKER.to_string()
+        } else {
+            answer.to_string()
+        }
+    };
+
+    let mut rows = Vec::new();
+    let mut nfailed = 0;
+    for day in days {
+        let path = data_path(day);
+        if !path.exists() {
+            continue;
+        }
+
+        match open(&path).and_then(|input| solve_day(day, input)) {
+            Ok((answer1, answer2)) => rows.push((day, compact(answer1), compact(answer2))),
+            Err(e) => {
+                eprintln!("day {:02}: {}", day, e);
+                nfailed += 1;
+            }
+        }
+    }
+
+    let width = rows.iter().map(|(_, answer1, _)| answer1.len()).max();
+    for (day, answer1, answer2) in &rows {
+        writeln!(
+            out,
+            "Day {:02}: {:<width$} | {}",
+            day,
+            answer1,
+            answer2,
+            width = width.unwrap_or(0)
+        )?;
+    }
+
+    if nfailed > 0 {
+        bail!("{} day(s) failed.", nfailed);
+    }
+
     Ok(())
 }
+
+/// Solves `day` `n` times, reading `input` only once, and prints the total and
+/// average time taken to stderr.
+fn solve_day_repeatedly(
+    day: usize,
+    mut input: Reader<'static>,
+    n: u32,
+) -> Result<(String, String), Error> {
+    if n == 0 {
+        bail!("Must solve the day at least once.");
+    }
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+
+    let mut answers = None;
+    let mut elapsed = Duration::default();
+    for _ in 0..n {
+        let input = Reader::from_bytes(bytes.clone());
+        let start = Instant::now();
+        answers = Some(solve_day(day, input)?);
+        elapsed += start.elapsed();
+    }
+    eprintln!(
+        "day {} ran {} times in {:.2?} ({:.2?} on average)",
+        day,
+        n,
+        elapsed,
+        elapsed / n
+    );
+    Ok(answers.unwrap())
+}
+
+fn solve_day_traced<P>(
+    day: usize,
+    input: Reader<'static>,
+    trace_file: P,
+) -> Result<(String, String), Error>
+where
+    P: AsRef<Path>,
+{
+    // Tracing stops at the first failed write, which is reported once the day is done.
+    let file = fs::File::create(trace_file)?;
+    let trace = Arc::new(Mutex::new((io::BufWriter::new(file), None)));
+    let hook_trace = Arc::clone(&trace);
+    let hooks = Hooks::with_trace(Box::new(move |pc, instruction| {
+        let (writer, error) = &mut *hook_trace.lock().unwrap();
+        if error.is_none() {
+            if let Err(e) = writeln!(writer, "{}: {}", pc, instruction) {
+                *error = Some(e);
+            }
+        }
+    }));
+    let answers = aoc2019::solve_day_with_hooks(day, input, &hooks);
+    let (writer, error) = &mut *trace.lock().unwrap();
+    if let Some(e) = error.take() {
+        return Err(e.into());
+    }
+    writer.flush()?;
+    answers
+}
+
+fn data_path(day: usize) -> PathBuf {
+    PathBuf::from(format!("data/{:02}.txt", day))
+}
+
+fn open<P>(path: P) -> Result<Reader<'static>, Error>
+where
+    P: AsRef<Path>,
+{
+    let file = fs::File::open(path)?;
+    let reader = io::BufReader::new(file);
+    Ok(Reader::File(reader))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("aoc2019").chain(args.iter().copied()))
+    }
+
+    /// Opens the puzzle input for `day`, or returns `None` (so the caller can skip
+    /// the test) if it hasn't been saved in `data/`, like the library's test helper
+    /// of the same name.
+    fn data_file(day: usize) -> Option<Reader<'static>> {
+        let path = data_path(day);
+        if !path.exists() {
+            eprintln!(
+                "Skipping checks that need {}, which does not exist.",
+                path.display()
+            );
+            return None;
+        }
+        Some(open(path).unwrap())
+    }
+
+    #[test]
+    fn test_parse_run() {
+        for args in &[
+            &["run", "5", "input.txt", "--time"][..],
+            &["5", "input.txt", "--time"],
+        ] {
+            let args = match parse(args).unwrap() {
+                Cli {
+                    command: Some(Command::Run(args)),
+                    run: None,
+                } => args,
+                Cli {
+                    command: None,
+                    run: Some(args),
+                } => args,
+                cli => panic!("unexpected parse: {:?}", cli),
+            };
+            assert_eq!(args.day, 5);
+            assert_eq!(args.input, Some(PathBuf::from("input.txt")));
+            assert!(args.time);
+            assert!(!args.json);
+            assert_eq!(args.program, None);
+            assert_eq!(args.trace_file, None);
+            assert_eq!(args.repeat, None);
+        }
+
+        let cli = parse(&[
+            "run",
+            "2",
+            "--program",
+            "1,0,0,0,99",
+            "--trace-file",
+            "t.txt",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Run(args)) => {
+                assert_eq!(args.program.as_deref(), Some("1,0,0,0,99"));
+                assert_eq!(args.trace_file, Some(PathBuf::from("t.txt")));
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        match parse(&["1", "--repeat", "10"]).unwrap().run {
+            Some(args) => assert_eq!(args.repeat, Some(10)),
+            run => panic!("unexpected run: {:?}", run),
+        }
+        assert!(parse(&["1", "--repeat", "0"]).is_err());
+        assert!(parse(&["1", "--repeat", "2", "--trace-file", "t.txt"]).is_err());
+        assert!(parse(&["run"]).is_err());
+        assert!(parse(&["run", "2", "input.txt", "--program", "99"]).is_err());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--time"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_all() {
+        match parse(&["all", "--summary"]).unwrap().command {
+            Some(Command::All(args)) => assert!(args.summary && !args.time),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        match parse(&["all", "--time"]).unwrap().command {
+            Some(Command::All(args)) => assert!(!args.summary && args.time),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        assert!(parse(&["all", "--summary", "--time"]).is_err());
+        assert!(parse(&["all", "5"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_bench() {
+        assert!(matches!(
+            parse(&["bench"]).unwrap().command,
+            Some(Command::Bench)
+        ));
+        assert!(parse(&["bench", "--time"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_disasm() {
+        match parse(&["disasm", "data/02.txt"]).unwrap().command {
+            Some(Command::Disasm(args)) => {
+                assert_eq!(args.input, Some(PathBuf::from("data/02.txt")))
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+        match parse(&["disasm"]).unwrap().command {
+            Some(Command::Disasm(args)) => assert_eq!(args.input, None),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_parse_repl() {
+        match parse(&["repl", "data/09.txt"]).unwrap().command {
+            Some(Command::Repl(args)) => assert_eq!(args.input, PathBuf::from("data/09.txt")),
+            command => panic!("unexpected command: {:?}", command),
+        }
+        assert!(parse(&["repl"]).is_err());
+    }
+
+    #[test]
+    fn test_benchmark_all() {
+        let mut buf = Vec::new();
+        benchmark_all(vec![1], &mut buf).unwrap();
+        let table = String::from_utf8(buf).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("| Day | Median (ms) |"));
+        assert_eq!(lines.next(), Some("| --- | ----------: |"));
+        // Days without a saved puzzle input are left out of the table.
+        if data_file(1).is_some() {
+            let row = lines.next().unwrap();
+            assert!(row.starts_with("| 01 | "));
+            assert!(row.ends_with(" |"));
+        }
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        let (Some(_), Some(_)) = (data_file(1), data_file(8)) else {
+            return;
+        };
+        let mut buf = Vec::new();
+        summary(vec![1, 8], &mut buf).unwrap();
+        let table = String::from_utf8(buf).unwrap();
+        let mut lines = table.lines();
+        assert_eq!(lines.next(), Some("Day 01: 3271095 | 4903759"));
+        assert_eq!(
+            lines.next(),
+            Some(&*format!("Day 08: {:<7} | {}", "1935", MULTILINE_MARKER))
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn test_solve_day_repeatedly() {
+        let answers = solve_day_repeatedly(1, Reader::from_bytes("12\n14"), 3).unwrap();
+        assert_eq!(answers, ("4".to_string(), "4".to_string()));
+        assert!(solve_day_repeatedly(1, Reader::from_bytes("12\n14"), 0).is_err());
+    }
+
+    #[test]
+    fn test_write_json() {
+        let (answer1, answer2) = aoc2019::run_day(1, "12\n14").unwrap();
+        let mut buf = Vec::new();
+        write_json(&mut buf, 1, &answer1, &answer2, None).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"day\":1,\"part1\":\"4\",\"part2\":\"4\"}\n"
+        );
+
+        let mut buf = Vec::new();
+        let elapsed = Some(Duration::from_micros(1500));
+        write_json(&mut buf, 8, "1", "# \"a\"\\\n\t", elapsed).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            concat!(
+                r##"{"day":8,"part1":"1","part2":"# \"a\"\\\n\t","elapsed_ms":1.500}"##,
+                "\n"
+            )
+        );
+
+        let cli = parse(&["run", "1", "--json", "--time"]).unwrap();
+        match cli.command {
+            Some(Command::Run(args)) => assert!(args.json && args.time),
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn test_trace_file() {
+        let Some(mut input) = data_file(2) else {
+            return;
+        };
+        let mut rom = String::new();
+        input.read_to_string(&mut rom).unwrap();
+        let rom = rom
+            .trim()
+            .split(',')
+            .map(|s| s.parse::<i64>().unwrap())
+            .collect::<Vec<_>>();
+        // Day 2's program is straight-line code of four-wide instructions ending in a
+        // halt. It's run once for part 1, and part 2 is solved with three runs plus
+        // one to check the answer.
+        let per_run = rom.chunks(4).position(|chunk| chunk[0] == 99).unwrap() + 1;
+        let nruns = 1 + 3 + 1;
+
+        let path = std::env::temp_dir().join("aoc2019-test-trace-02.txt");
+        let input = data_file(2).unwrap();
+        solve_day_traced(2, input, &path).unwrap();
+        let trace = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(trace.lines().count(), per_run * nruns);
+        assert_eq!(trace.lines().next(), Some("0: ADD [12], [2], [3]"));
+        assert_eq!(
+            trace.lines().last(),
+            Some(&*format!("{}: HALT", (per_run - 1) * 4))
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::cmp::Ordering;
+
+use crate::error::Error;
+use crate::utils::Vec3;
+
+/// A single body in an n-body system, with integer position and velocity.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Body {
+    pos: Vec3<i64>,
+    vel: Vec3<i64>,
+}
+
+impl Body {
+    pub fn new(pos: [i64; 3], vel: [i64; 3]) -> Self {
+        Self {
+            pos: pos.into(),
+            vel: vel.into(),
+        }
+    }
+
+    pub fn pos(&self) -> [i64; 3] {
+        [self.pos.x(), self.pos.y(), self.pos.z()]
+    }
+
+    pub fn vel(&self) -> [i64; 3] {
+        [self.vel.x(), self.vel.y(), self.vel.z()]
+    }
+
+    /// Potential energy (sum of absolute position components) times kinetic energy
+    /// (sum of absolute velocity components).
+    pub fn energy(&self) -> u64 {
+        let potential = self.pos.iter().map(|p| p.unsigned_abs()).sum::<u64>();
+        let kinetic = self.vel.iter().map(|v| v.unsigned_abs()).sum::<u64>();
+        potential * kinetic
+    }
+}
+
+/// A set of bodies that pull on each other one unit of velocity per step along
+/// each axis, as in day 12.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct System {
+    bodies: Vec<Body>,
+}
+
+impl System {
+    pub fn new(bodies: Vec<Body>) -> Self {
+        Self { bodies }
+    }
+
+    pub fn bodies(&self) -> &[Body] {
+        &self.bodies
+    }
+
+    /// Applies gravity between every pair of bodies, then velocity to every body.
+    pub fn step(&mut self) {
+        for axis in 0..3 {
+            self.step_axis(axis);
+        }
+    }
+
+    pub fn energy(&self) -> u64 {
+        self.bodies.iter().map(Body::energy).sum()
+    }
+
+    /// Number of steps after which the positions and velocities along `axis` first
+    /// return to their current values. Axes are independent of each other, so the
+    /// period of the whole system is the lcm of its three axis periods.
+    pub fn axis_period(&self, axis: usize) -> Result<u64, Error> {
+        if axis > 2 {
+            bail!("Axis must be 0, 1, or 2, but got {}.", axis);
+        }
+        // The sum of positions along an axis moves by the sum of velocities every step,
+        // so unless that sum is zero the axis can never return to where it started.
+        if self.bodies.iter().map(|body| body.vel[axis]).sum::<i64>() != 0 {
+            bail!(
+                "Axis {} never repeats since its total velocity is nonzero.",
+                axis
+            );
+        }
+
+        let mut system = self.clone();
+        let mut nsteps = 0;
+        loop {
+            system.step_axis(axis);
+            nsteps += 1;
+            if system
+                .bodies
+                .iter()
+                .zip(self.bodies.iter())
+                .all(|(a, b)| a.pos[axis] == b.pos[axis] && a.vel[axis] == b.vel[axis])
+            {
+                return Ok(nsteps);
+            }
+        }
+    }
+
+    fn step_axis(&mut self, axis: usize) {
+        for i in 0..self.bodies.len() {
+            for j in (i + 1)..self.bodies.len() {
+                match self.bodies[i].pos[axis].cmp(&self.bodies[j].pos[axis]) {
+                    Ordering::Less => {
+                        self.bodies[i].vel[axis] += 1;
+                        self.bodies[j].vel[axis] -= 1;
+                    }
+                    Ordering::Greater => {
+                        self.bodies[i].vel[axis] -= 1;
+                        self.bodies[j].vel[axis] += 1;
+                    }
+                    Ordering::Equal => {}
+                }
+            }
+        }
+        for body in self.bodies.iter_mut() {
+            body.pos[axis] += body.vel[axis];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_bodies() {
+        let mut system = System::new(vec![
+            Body::new([0, 0, 0], [0, 0, 0]),
+            Body::new([3, 0, 0], [0, 0, 0]),
+        ]);
+        assert_eq!(system.axis_period(0).unwrap(), 8);
+        assert_eq!(system.axis_period(1).unwrap(), 1);
+        assert!(system.axis_period(3).is_err());
+
+        system.step();
+        assert_eq!(system.bodies()[0], Body::new([1, 0, 0], [1, 0, 0]));
+        assert_eq!(system.bodies()[1], Body::new([2, 0, 0], [-1, 0, 0]));
+        assert_eq!(system.energy(), 3);
+
+        for _ in 0..3 {
+            system.step();
+        }
+        assert_eq!(system.energy(), 0);
+    }
+
+    #[test]
+    fn test_drifting_axis() {
+        let system = System::new(vec![Body::new([0, 0, 0], [1, 0, 0])]);
+        assert!(system.axis_period(0).is_err());
+    }
+
+    #[test]
+    fn test_example() {
+        let mut system = System::new(
+            [[-1, 0, 2], [2, -10, -7], [4, -8, 8], [3, 5, -1]]
+                .iter()
+                .map(|&pos| Body::new(pos, [0, 0, 0]))
+                .collect(),
+        );
+        let periods = (0..3)
+            .map(|axis| system.axis_period(axis).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(periods, vec![18, 28, 44]);
+
+        for _ in 0..10 {
+            system.step();
+        }
+        assert_eq!(system.energy(), 179);
+    }
+}
@@ -1,25 +1,42 @@
+use std::cmp;
 use std::convert::TryFrom;
 use std::hash::Hash;
-use std::ops::{Deref, DerefMut};
+use std::io;
+use std::ops::{Add, Deref, DerefMut, Mul, Sub};
 
 use crate::error::Error;
 
 pub(crate) mod math {
     use super::*;
 
-    pub(crate) fn fact(mut n: usize) -> Result<usize, Error> {
-        let orig = n;
+    #[allow(unused)]
+    pub(crate) fn fact(n: usize) -> Result<usize, Error> {
         let mut answer = 1usize;
-        loop {
-            answer = match answer.checked_mul(n) {
+        for i in 2..=n {
+            answer = match answer.checked_mul(i) {
                 Some(val) => val,
-                None => bail!("Factorial of {} overflows usize.", orig),
+                None => bail!("Factorial of {} overflows usize.", n),
+            };
+        }
+        Ok(answer)
+    }
+
+    /// Number of ordered ways to pick `k` of `n` distinct items, i.e. `n! / (n - k)!`,
+    /// computed as a falling product so it doesn't overflow when only `n!` would.
+    pub(crate) fn npermutations(n: usize, k: usize) -> Result<usize, Error> {
+        if k > n {
+            bail!("Cannot pick {} of {} items.", k, n);
+        }
+        let mut answer = 1usize;
+        for i in (n - k + 1)..=n {
+            answer = match answer.checked_mul(i) {
+                Some(val) => val,
+                None => bail!(
+                    "Number of permutations of {} of {} items overflows usize.",
+                    k,
+                    n
+                ),
             };
-            if (n - 1) == 1 {
-                break;
-            } else {
-                n -= 1;
-            }
         }
         Ok(answer)
     }
@@ -40,7 +57,82 @@ pub(crate) mod math {
     }
 
     pub(crate) fn lcm(a: u64, b: u64) -> Result<u64, Error> {
-        Ok(a * b / gcf(a, b)?)
+        match (a / gcf(a, b)?).checked_mul(b) {
+            Some(val) => Ok(val),
+            None => bail!("lcm of {} and {} overflows u64.", a, b),
+        }
+    }
+
+    pub(crate) fn lcm_all<I>(iter: I) -> Result<u64, Error>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        fold_all(iter, lcm, "lcm_all")
+    }
+
+    #[allow(unused)]
+    pub(crate) fn gcf_all<I>(iter: I) -> Result<u64, Error>
+    where
+        I: IntoIterator<Item = u64>,
+    {
+        fold_all(iter, gcf, "gcf_all")
+    }
+
+    fn fold_all<I, F>(iter: I, f: F, name: &str) -> Result<u64, Error>
+    where
+        I: IntoIterator<Item = u64>,
+        F: Fn(u64, u64) -> Result<u64, Error>,
+    {
+        let mut iter = iter.into_iter();
+        let first = match iter.next() {
+            Some(val) => val,
+            None => bail!("{} function requires at least one input.", name),
+        };
+        iter.try_fold(first, f)
+    }
+
+    /// Solves the system `x ≡ r_i (mod m_i)` for each `(r_i, m_i)` in `residues`,
+    /// returning the smallest non-negative solution. Moduli need not be pairwise
+    /// coprime, but the system must be consistent.
+    #[allow(unused)]
+    pub(crate) fn crt(residues: &[(i128, i128)]) -> Result<i128, Error> {
+        let (mut x, mut m) = (0i128, 1i128);
+        for &(r, n) in residues {
+            if n <= 0 {
+                bail!("crt function only works with positive moduli.");
+            }
+            let (g, p, _) = egcd(m, n);
+            let diff = r - x;
+            if diff % g != 0 {
+                bail!(
+                    "No solution for x ≡ {} (mod {}) given x ≡ {} (mod {}).",
+                    r,
+                    n,
+                    x,
+                    m
+                );
+            }
+            let n_g = n / g;
+            let k = ((diff / g) % n_g * (p % n_g)) % n_g;
+            let lcm = match m.checked_mul(n_g) {
+                Some(val) => val,
+                None => bail!("Combined modulus of crt overflows i128."),
+            };
+            x = (x + m * k).rem_euclid(lcm);
+            m = lcm;
+        }
+        Ok(x)
+    }
+
+    /// Extended Euclidean algorithm. Returns `(g, p, q)` such that
+    /// `a * p + b * q == g`, where `g` is the greatest common factor of `a` and `b`.
+    fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
+        if b == 0 {
+            (a, 1, 0)
+        } else {
+            let (g, p, q) = egcd(b, a % b);
+            (g, q, p - (a / b) * q)
+        }
     }
 
     #[cfg(test)]
@@ -54,20 +146,744 @@ pub(crate) mod math {
             assert_eq!(3, gcf(15, 21).unwrap());
             assert!(gcf(1, 0).is_err());
         }
+
+        #[test]
+        fn test_fact() {
+            assert_eq!(1, fact(0).unwrap());
+            assert_eq!(1, fact(1).unwrap());
+            assert_eq!(120, fact(5).unwrap());
+            assert!(fact(21).is_err());
+        }
+
+        #[test]
+        fn test_npermutations() {
+            assert_eq!(1, npermutations(5, 0).unwrap());
+            assert_eq!(20, npermutations(5, 2).unwrap());
+            assert_eq!(fact(5).unwrap(), npermutations(5, 5).unwrap());
+            assert_eq!(17_100_720, npermutations(30, 5).unwrap());
+            assert!(npermutations(2, 3).is_err());
+            assert!(npermutations(usize::MAX, 2).is_err());
+        }
+
+        #[test]
+        fn test_lcm_all_gcf_all() {
+            assert_eq!(24, lcm_all(vec![4, 6, 8]).unwrap());
+            assert_eq!(7, lcm_all(Some(7)).unwrap());
+            assert!(lcm_all(vec![]).is_err());
+            assert_eq!(2, gcf_all(vec![4, 6, 8]).unwrap());
+            assert!(gcf_all(vec![]).is_err());
+        }
+
+        #[test]
+        fn test_lcm_large() {
+            let (a, b) = (1 << 62, 3 << 61);
+            assert_eq!(3 << 62, lcm(a, b).unwrap());
+            assert!(lcm(u64::MAX, u64::MAX - 1).is_err());
+        }
+
+        #[test]
+        fn test_crt() {
+            assert_eq!(23, crt(&[(2, 3), (3, 5), (2, 7)]).unwrap());
+            assert_eq!(0, crt(&[]).unwrap());
+            assert_eq!(11, crt(&[(5, 6), (3, 4)]).unwrap());
+            assert!(crt(&[(1, 4), (2, 6)]).is_err());
+            assert!(crt(&[(1, 0)]).is_err());
+        }
     }
 }
 
+pub(crate) mod combinatorics {
+    use super::*;
+
+    /// Every ordering of `items`, generated by Heap's algorithm (so each one differs
+    /// from the one before by a single swap). There are `n!` of them, including the
+    /// single empty ordering when `items` is empty.
+    #[allow(unused)]
+    pub(crate) fn permutations<T>(items: &[T]) -> impl Iterator<Item = Vec<T>>
+    where
+        T: Clone,
+    {
+        Permutations {
+            items: items.to_vec(),
+            c: vec![0; items.len()],
+            i: 1,
+            first: true,
+        }
+    }
+
+    /// Every subset of `items`, in the order of the bitmasks `0..2^n` that pick them
+    /// (bit `i` set means `items[i]` is included), so starting with the empty set and
+    /// ending with all of `items`. There can be at most 63 items.
+    #[allow(unused)]
+    pub(crate) fn subsets<T>(items: &[T]) -> Result<impl Iterator<Item = Vec<T>> + '_, Error>
+    where
+        T: Clone,
+    {
+        check_len(items)?;
+        Ok((0..1u64 << items.len()).map(move |mask| pick(items, mask)))
+    }
+
+    /// Every subset of `items` with exactly `k` elements, in the same order as
+    /// `subsets`. There can be at most 63 items.
+    #[allow(unused)]
+    pub(crate) fn subsets_of_size<T>(
+        items: &[T],
+        k: usize,
+    ) -> Result<impl Iterator<Item = Vec<T>> + '_, Error>
+    where
+        T: Clone,
+    {
+        check_len(items)?;
+        let end = 1u64 << items.len();
+        let first = if k <= items.len() {
+            Some((1u64 << k) - 1)
+        } else {
+            None
+        };
+        // Gosper's hack: the next larger number with the same number of bits set.
+        let masks = std::iter::successors(first, move |&mask| {
+            if mask == 0 {
+                return None;
+            }
+            let lowest = mask & mask.wrapping_neg();
+            let ripple = mask + lowest;
+            Some((((ripple ^ mask) >> 2) / lowest) | ripple)
+        })
+        .take_while(move |&mask| mask < end);
+        Ok(masks.map(move |mask| pick(items, mask)))
+    }
+
+    fn check_len<T>(items: &[T]) -> Result<(), Error> {
+        if items.len() > 63 {
+            bail!(
+                "Cannot take subsets of {} items; there can be at most 63.",
+                items.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn pick<T>(items: &[T], mask: u64) -> Vec<T>
+    where
+        T: Clone,
+    {
+        items
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, item)| item.clone())
+            .collect()
+    }
+
+    struct Permutations<T> {
+        items: Vec<T>,
+        // The iterative form of Heap's algorithm's stack of loop counters.
+        c: Vec<usize>,
+        i: usize,
+        first: bool,
+    }
+
+    impl<T> Iterator for Permutations<T>
+    where
+        T: Clone,
+    {
+        type Item = Vec<T>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.first {
+                self.first = false;
+                return Some(self.items.clone());
+            }
+            while self.i < self.items.len() {
+                let i = self.i;
+                if self.c[i] < i {
+                    if i.is_multiple_of(2) {
+                        self.items.swap(0, i);
+                    } else {
+                        self.items.swap(self.c[i], i);
+                    }
+                    self.c[i] += 1;
+                    self.i = 1;
+                    return Some(self.items.clone());
+                }
+                self.c[i] = 0;
+                self.i += 1;
+            }
+            None
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::HashSet;
+
+        use super::*;
+        use crate::utils::math;
+
+        #[test]
+        fn test_permutations() {
+            for n in 0..=4 {
+                let items = (0..n).collect::<Vec<_>>();
+                let all = permutations(&items).collect::<Vec<_>>();
+                assert_eq!(all.len(), math::fact(n).unwrap());
+                assert!(all.iter().all(|p| {
+                    let mut sorted = p.clone();
+                    sorted.sort();
+                    sorted == items
+                }));
+                let distinct = all.into_iter().collect::<HashSet<_>>();
+                assert_eq!(distinct.len(), math::fact(n).unwrap());
+            }
+
+            let all = permutations(&['a', 'b', 'c']).collect::<Vec<_>>();
+            assert_eq!(all[0], vec!['a', 'b', 'c']);
+            assert_eq!(all[1], vec!['b', 'a', 'c']);
+        }
+
+        #[test]
+        fn test_subsets() {
+            let all = subsets(&['a', 'b', 'c']).unwrap().collect::<Vec<_>>();
+            let expected: Vec<Vec<char>> = vec![
+                vec![],
+                vec!['a'],
+                vec!['b'],
+                vec!['a', 'b'],
+                vec!['c'],
+                vec!['a', 'c'],
+                vec!['b', 'c'],
+                vec!['a', 'b', 'c'],
+            ];
+            assert_eq!(all, expected);
+            assert_eq!(subsets::<u8>(&[]).unwrap().count(), 1);
+
+            for k in 0..=3 {
+                let of_size = subsets_of_size(&['a', 'b', 'c'], k)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+                let filtered = expected
+                    .iter()
+                    .filter(|s| s.len() == k)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                assert_eq!(of_size, filtered);
+            }
+            assert_eq!(subsets_of_size(&['a'], 2).unwrap().count(), 0);
+            assert_eq!(subsets_of_size(&[0; 63], 62).unwrap().count(), 63);
+
+            assert!(subsets(&[0; 64]).is_err());
+            assert!(subsets_of_size(&[0; 64], 1).is_err());
+        }
+    }
+}
+
+pub(crate) mod direction {
+    use super::Vec2;
+
+    /// A compass direction on a grid where north is `+y` and east is `+x`.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    pub(crate) enum Direction {
+        North,
+        South,
+        East,
+        West,
+    }
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+    pub(crate) enum Turn {
+        Left,
+        Right,
+    }
+
+    impl Direction {
+        pub(crate) fn turn(self, turn: Turn) -> Direction {
+            use self::Direction::*;
+            use self::Turn::*;
+            match (self, turn) {
+                (North, Left) => West,
+                (North, Right) => East,
+                (South, Left) => East,
+                (South, Right) => West,
+                (East, Left) => North,
+                (East, Right) => South,
+                (West, Left) => South,
+                (West, Right) => North,
+            }
+        }
+
+        pub(crate) fn opposite(self) -> Direction {
+            use self::Direction::*;
+            match self {
+                North => South,
+                South => North,
+                East => West,
+                West => East,
+            }
+        }
+
+        /// The unit vector pointing in this direction.
+        pub(crate) fn delta(self) -> Vec2<i64> {
+            use self::Direction::*;
+            match self {
+                North => Vec2::new(0, 1),
+                South => Vec2::new(0, -1),
+                East => Vec2::new(1, 0),
+                West => Vec2::new(-1, 0),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_turn() {
+            use self::Direction::*;
+            use self::Turn::*;
+            let test_cases = &[
+                // (direction, turn, expected)
+                (North, Left, West),
+                (West, Left, South),
+                (South, Left, East),
+                (East, Left, North),
+                (North, Right, East),
+                (East, Right, South),
+                (South, Right, West),
+                (West, Right, North),
+            ];
+
+            for (direction, turn, expected) in test_cases {
+                assert_eq!(direction.turn(*turn), *expected);
+                assert_eq!(expected.turn(*turn).opposite(), *direction);
+            }
+        }
+
+        #[test]
+        fn test_delta() {
+            let origin = Vec2::new(0, 0);
+            let point = origin + Direction::North.delta() + Direction::East.delta();
+            assert_eq!(point, Vec2::new(1, 1));
+            assert_eq!(point + Direction::South.delta() * 2, Vec2::new(1, -1));
+            assert_eq!(Direction::West.delta(), Direction::East.delta() * -1);
+        }
+    }
+}
+
+pub(crate) mod grid {
+    use std::fmt;
+    use std::io;
+
+    use crate::error::Error;
+
+    /// A rectangular grid stored row by row in a flat `Vec`, with `(0, 0)` at the top
+    /// left and `y` increasing downwards.
+    #[derive(Clone, Debug, Eq, PartialEq, Hash)]
+    pub(crate) struct Grid<T> {
+        cells: Vec<T>,
+        width: usize,
+        height: usize,
+    }
+
+    impl<T> Grid<T>
+    where
+        T: Clone,
+    {
+        pub(crate) fn new(width: usize, height: usize, fill: T) -> Self {
+            Self {
+                cells: vec![fill; width * height],
+                width,
+                height,
+            }
+        }
+    }
+
+    impl<T> Grid<T> {
+        pub(crate) fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Result<Self, Error> {
+            if cells.len() != width * height {
+                bail!(
+                    "Cannot make a {}x{} grid out of {} cells.",
+                    width,
+                    height,
+                    cells.len()
+                );
+            }
+            Ok(Self {
+                cells,
+                width,
+                height,
+            })
+        }
+
+        /// Builds a grid from lines of text, mapping each character to a cell with `f`.
+        /// Every line must be the same length.
+        #[allow(unused)]
+        pub(crate) fn parse<R, F>(reader: R, mut f: F) -> Result<Self, Error>
+        where
+            R: io::BufRead,
+            F: FnMut(char) -> Result<T, Error>,
+        {
+            let mut cells = Vec::new();
+            let (mut width, mut height) = (0, 0);
+            for line in reader.lines() {
+                let line = line?;
+                let line = line.trim_end();
+                if line.is_empty() {
+                    continue;
+                }
+                let len = line.chars().count();
+                if height == 0 {
+                    width = len;
+                } else if len != width {
+                    bail!(
+                        "Line {} of the grid has {} characters, but expected {}.",
+                        height + 1,
+                        len,
+                        width
+                    );
+                }
+                for c in line.chars() {
+                    cells.push(f(c)?);
+                }
+                height += 1;
+            }
+            Ok(Self {
+                cells,
+                width,
+                height,
+            })
+        }
+
+        #[allow(unused)]
+        pub(crate) fn width(&self) -> usize {
+            self.width
+        }
+
+        #[allow(unused)]
+        pub(crate) fn height(&self) -> usize {
+            self.height
+        }
+
+        pub(crate) fn in_bounds(&self, x: usize, y: usize) -> bool {
+            x < self.width && y < self.height
+        }
+
+        #[allow(unused)]
+        pub(crate) fn get(&self, x: usize, y: usize) -> Option<&T> {
+            if self.in_bounds(x, y) {
+                self.cells.get(y * self.width + x)
+            } else {
+                None
+            }
+        }
+
+        pub(crate) fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+            if self.in_bounds(x, y) {
+                self.cells.get_mut(y * self.width + x)
+            } else {
+                None
+            }
+        }
+
+        /// The in-bounds points directly above, below, left, and right of `(x, y)`.
+        #[allow(unused)]
+        pub(crate) fn neighbors4(
+            &self,
+            x: usize,
+            y: usize,
+        ) -> impl Iterator<Item = (usize, usize)> {
+            let (width, height) = (self.width, self.height);
+            let candidates = [
+                (x.checked_sub(1), Some(y)),
+                (x.checked_add(1), Some(y)),
+                (Some(x), y.checked_sub(1)),
+                (Some(x), y.checked_add(1)),
+            ];
+            IntoIterator::into_iter(candidates).filter_map(move |point| match point {
+                (Some(x), Some(y)) if x < width && y < height => Some((x, y)),
+                _ => None,
+            })
+        }
+
+        pub(crate) fn rows(&self) -> impl Iterator<Item = &[T]> {
+            // `chunks` panics on a zero chunk size, which an empty grid would ask for.
+            self.cells.chunks(self.width.max(1))
+        }
+    }
+
+    /// Draws each row on its own line.
+    impl<T> fmt::Display for Grid<T>
+    where
+        T: Copy + Into<char>,
+    {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for row in self.rows() {
+                let line = row.iter().map(|&cell| cell.into()).collect::<String>();
+                writeln!(f, "{}", line)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_parse() {
+            let input = ".#.\n##.\n";
+            let grid = Grid::parse(input.as_bytes(), |c| match c {
+                '.' => Ok(false),
+                '#' => Ok(true),
+                _ => bail!("Bad cell {:?}", c),
+            })
+            .unwrap();
+            assert_eq!((grid.width(), grid.height()), (3, 2));
+            assert_eq!(grid.get(1, 0), Some(&true));
+            assert_eq!(grid.get(2, 1), Some(&false));
+            assert_eq!(grid.get(3, 0), None);
+            assert_eq!(grid.get(0, 2), None);
+
+            let chars = Grid::parse(input.as_bytes(), Ok).unwrap();
+            assert_eq!(chars.to_string(), input);
+
+            assert!(Grid::parse("..\n.\n".as_bytes(), Ok).is_err());
+            assert!(Grid::parse(".x".as_bytes(), |c| match c {
+                '.' => Ok(()),
+                _ => bail!("Bad cell {:?}", c),
+            })
+            .is_err());
+        }
+
+        #[test]
+        fn test_get_mut() {
+            let mut grid = Grid::new(2, 2, ' ');
+            *grid.get_mut(1, 0).unwrap() = '#';
+            assert!(grid.get_mut(2, 0).is_none());
+            assert_eq!(grid.to_string(), " #\n  \n");
+            assert!(Grid::from_vec(2, 2, vec![' '; 3]).is_err());
+        }
+
+        #[test]
+        fn test_neighbors4() {
+            let grid = Grid::new(3, 2, 0);
+            let mut corner = grid.neighbors4(0, 0).collect::<Vec<_>>();
+            corner.sort();
+            assert_eq!(corner, vec![(0, 1), (1, 0)]);
+
+            let mut edge = grid.neighbors4(1, 1).collect::<Vec<_>>();
+            edge.sort();
+            assert_eq!(edge, vec![(0, 1), (1, 0), (2, 1)]);
+
+            assert_eq!(Grid::new(1, 1, 0).neighbors4(0, 0).count(), 0);
+        }
+    }
+}
+
+pub(crate) mod search {
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashMap, VecDeque};
+    use std::hash::Hash;
+
+    /// Breadth-first search from `start`, returning the number of steps from `start`
+    /// to every reachable node (including `start` itself, at zero).
+    pub(crate) fn bfs<N, F, I>(start: N, mut neighbors: F) -> HashMap<N, usize>
+    where
+        N: Clone + Eq + Hash,
+        F: FnMut(&N) -> I,
+        I: IntoIterator<Item = N>,
+    {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(start.clone(), 0);
+        queue.push_back((start, 0));
+        while let Some((node, distance)) = queue.pop_front() {
+            for neighbor in neighbors(&node) {
+                if !distances.contains_key(&neighbor) {
+                    distances.insert(neighbor.clone(), distance + 1);
+                    queue.push_back((neighbor, distance + 1));
+                }
+            }
+        }
+        distances
+    }
+
+    /// The nodes along a shortest path from `start` to `goal`, including both ends, or
+    /// `None` if `goal` can't be reached.
+    pub(crate) fn shortest_path<N, F, I>(start: N, goal: &N, mut neighbors: F) -> Option<Vec<N>>
+    where
+        N: Clone + Eq + Hash,
+        F: FnMut(&N) -> I,
+        I: IntoIterator<Item = N>,
+    {
+        let mut predecessors: HashMap<N, Option<N>> = HashMap::new();
+        let mut queue = VecDeque::new();
+        predecessors.insert(start.clone(), None);
+        queue.push_back(start);
+        while let Some(node) = queue.pop_front() {
+            if &node == goal {
+                let mut path = vec![node];
+                while let Some(Some(predecessor)) = predecessors.get(path.last().unwrap()) {
+                    path.push(predecessor.clone());
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for neighbor in neighbors(&node) {
+                if !predecessors.contains_key(&neighbor) {
+                    predecessors.insert(neighbor.clone(), Some(node.clone()));
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        None
+    }
+
+    /// Dijkstra's algorithm from `start`, where `neighbors` yields each neighbor along
+    /// with the cost of the edge to it. Returns the least total cost from `start` to
+    /// every reachable node.
+    #[allow(unused)]
+    pub(crate) fn dijkstra<N, F, I>(start: N, mut neighbors: F) -> HashMap<N, usize>
+    where
+        N: Clone + Eq + Hash + Ord,
+        F: FnMut(&N) -> I,
+        I: IntoIterator<Item = (N, usize)>,
+    {
+        let mut distances = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        distances.insert(start.clone(), 0);
+        heap.push(Reverse((0, start)));
+        while let Some(Reverse((distance, node))) = heap.pop() {
+            if distances.get(&node).is_some_and(|&best| distance > best) {
+                continue;
+            }
+            for (neighbor, cost) in neighbors(&node) {
+                let candidate = distance + cost;
+                if distances
+                    .get(&neighbor)
+                    .is_none_or(|&best| candidate < best)
+                {
+                    distances.insert(neighbor.clone(), candidate);
+                    heap.push(Reverse((candidate, neighbor)));
+                }
+            }
+        }
+        distances
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // 0 - 1 - 2 - 3
+        //     |       |
+        //     4 ----- 5     6
+        fn neighbors(node: &u8) -> Vec<u8> {
+            match node {
+                0 => vec![1],
+                1 => vec![0, 2, 4],
+                2 => vec![1, 3],
+                3 => vec![2, 5],
+                4 => vec![1, 5],
+                5 => vec![3, 4],
+                _ => vec![],
+            }
+        }
+
+        #[test]
+        fn test_bfs() {
+            let distances = bfs(0, neighbors);
+            let mut distances = distances.into_iter().collect::<Vec<_>>();
+            distances.sort();
+            assert_eq!(
+                distances,
+                vec![(0, 0), (1, 1), (2, 2), (3, 3), (4, 2), (5, 3)]
+            );
+            assert_eq!(bfs(6, neighbors).len(), 1);
+        }
+
+        #[test]
+        fn test_shortest_path() {
+            assert_eq!(shortest_path(0, &3, neighbors), Some(vec![0, 1, 2, 3]));
+            assert_eq!(shortest_path(5, &0, neighbors).unwrap().len(), 4);
+            assert_eq!(shortest_path(2, &2, neighbors), Some(vec![2]));
+            assert_eq!(shortest_path(0, &6, neighbors), None);
+        }
+
+        #[test]
+        fn test_dijkstra() {
+            // The direct edge from a to c costs more than going around through b.
+            let weighted = |node: &char| match node {
+                'a' => vec![('b', 1), ('c', 10)],
+                'b' => vec![('c', 2), ('d', 7)],
+                'c' => vec![('d', 1)],
+                _ => vec![],
+            };
+            let distances = dijkstra('a', weighted);
+            assert_eq!(distances[&'a'], 0);
+            assert_eq!(distances[&'b'], 1);
+            assert_eq!(distances[&'c'], 3);
+            assert_eq!(distances[&'d'], 4);
+            assert!(!distances.contains_key(&'e'));
+        }
+    }
+}
+
+/// Calls `f` with the (1-based) line number and contents of every line read from
+/// `reader` that isn't blank, trimmed of surrounding whitespace, stopping at the
+/// first error from either. Blank lines still count towards the line number.
+pub(crate) fn for_each_line<R, F>(mut reader: R, mut f: F) -> Result<(), Error>
+where
+    R: io::BufRead,
+    F: FnMut(usize, &str) -> Result<(), Error>,
+{
+    let mut buffer = String::new();
+    let mut lineno = 0;
+    loop {
+        buffer.clear();
+        if reader.read_line(&mut buffer)? == 0 {
+            return Ok(());
+        }
+        lineno += 1;
+        let line = buffer.trim();
+        if !line.is_empty() {
+            f(lineno, line)?;
+        }
+    }
+}
+
+/// An `f64` that is never NaN and never negative zero, which makes it totally
+/// ordered and lets it be hashed by its bits.
+///
+/// `==` (and so hashing) is exact: values computed in different ways that ought to
+/// be the same, like `0.1 + 0.2` and `0.3`, may not be equal. Use it where values
+/// come from the same computation on the same inputs, e.g. as a `HashMap` key, and
+/// use `approx_eq` where rounding error can creep in.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) struct F64(f64);
 
+impl F64 {
+    /// Wraps `f`, or returns `None` if it is NaN.
+    pub(crate) fn new(f: f64) -> Option<Self> {
+        if f.is_nan() {
+            return None;
+        }
+        // Adding positive zero turns negative zero into positive zero and leaves every
+        // other value alone.
+        Some(F64(f + 0.0))
+    }
+
+    /// Whether `self` and `other` are within `eps` of each other.
+    #[allow(unused)]
+    pub(crate) fn approx_eq(&self, other: &Self, eps: f64) -> bool {
+        (self.0 - other.0).abs() <= eps
+    }
+}
+
 impl TryFrom<f64> for F64 {
     type Error = Error;
 
     fn try_from(f: f64) -> Result<Self, Self::Error> {
-        if f.is_nan() {
-            bail!("Cannot convert {} into F64", f);
-        }
-        Ok(F64(f))
+        F64::new(f).ok_or_else(|| error!("Cannot convert {} into F64", f))
     }
 }
 
@@ -81,7 +897,20 @@ impl Deref for F64 {
 
 impl Eq for F64 {}
 
-// TODO: Verify that this is kosher.
+impl PartialOrd for F64 {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for F64 {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        // Without NaN or negative zero, `total_cmp` agrees with `==`.
+        self.0.total_cmp(&other.0)
+    }
+}
+
+// Equal values have equal bits, since negative zero is ruled out.
 #[allow(clippy::derive_hash_xor_eq)]
 impl Hash for F64 {
     fn hash<H>(&self, state: &mut H)
@@ -114,6 +943,82 @@ where
     }
 }
 
+impl Vec2<i64> {
+    /// The four points directly north, south, east, and west of this one, in that
+    /// order, with north being positive y.
+    pub(crate) fn neighbors4(&self) -> [Self; 4] {
+        let Self(x, y) = *self;
+        [
+            Self(x, y + 1),
+            Self(x, y - 1),
+            Self(x + 1, y),
+            Self(x - 1, y),
+        ]
+    }
+
+    /// The eight points around this one, clockwise starting from north.
+    #[allow(unused)]
+    pub(crate) fn neighbors8(&self) -> [Self; 8] {
+        let Self(x, y) = *self;
+        [
+            Self(x, y + 1),
+            Self(x + 1, y + 1),
+            Self(x + 1, y),
+            Self(x + 1, y - 1),
+            Self(x, y - 1),
+            Self(x - 1, y - 1),
+            Self(x - 1, y),
+            Self(x - 1, y + 1),
+        ]
+    }
+}
+
+impl<T> Vec2<T>
+where
+    T: Copy + Ord + Add<Output = T> + Sub<Output = T>,
+{
+    /// Manhattan (taxicab) distance between two points.
+    #[allow(unused)]
+    pub(crate) fn manhattan(&self, other: &Self) -> T {
+        let dx = cmp::max(self.0, other.0) - cmp::min(self.0, other.0);
+        let dy = cmp::max(self.1, other.1) - cmp::min(self.1, other.1);
+        dx + dy
+    }
+}
+
+impl<T> Add for Vec2<T>
+where
+    T: Add<Output = T>,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
+impl<T> Sub for Vec2<T>
+where
+    T: Sub<Output = T>,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self(self.0 - rhs.0, self.1 - rhs.1)
+    }
+}
+
+impl<T> Mul<T> for Vec2<T>
+where
+    T: Copy + Mul<Output = T>,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Self(self.0 * rhs, self.1 * rhs)
+    }
+}
+
 impl<T> From<(T, T)> for Vec2<T> {
     fn from(tup: (T, T)) -> Self {
         Self(tup.0, tup.1)
@@ -159,6 +1064,75 @@ where
     }
 }
 
+impl<T> Vec3<T>
+where
+    T: Copy + Add<Output = T> + Mul<Output = T>,
+{
+    #[allow(unused)]
+    pub(crate) fn dot(&self, other: &Self) -> T {
+        self.0[0] * other.0[0] + self.0[1] * other.0[1] + self.0[2] * other.0[2]
+    }
+}
+
+impl<T> Vec3<T>
+where
+    T: Copy + Default + Ord + Add<Output = T> + Sub<Output = T>,
+{
+    /// Sum of the absolute values of the components, e.g. a body's potential or
+    /// kinetic energy in day 12.
+    pub(crate) fn abs_sum(&self) -> T {
+        let abs = |n: T| {
+            if n < T::default() {
+                T::default() - n
+            } else {
+                n
+            }
+        };
+        abs(self.0[0]) + abs(self.0[1]) + abs(self.0[2])
+    }
+}
+
+impl<T> Add for Vec3<T>
+where
+    T: Copy + Add<Output = T>,
+{
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self([
+            self.0[0] + rhs.0[0],
+            self.0[1] + rhs.0[1],
+            self.0[2] + rhs.0[2],
+        ])
+    }
+}
+
+impl<T> Sub for Vec3<T>
+where
+    T: Copy + Sub<Output = T>,
+{
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self([
+            self.0[0] - rhs.0[0],
+            self.0[1] - rhs.0[1],
+            self.0[2] - rhs.0[2],
+        ])
+    }
+}
+
+impl<T> Mul<T> for Vec3<T>
+where
+    T: Copy + Mul<Output = T>,
+{
+    type Output = Self;
+
+    fn mul(self, rhs: T) -> Self::Output {
+        Self([self.0[0] * rhs, self.0[1] * rhs, self.0[2] * rhs])
+    }
+}
+
 impl<T> Deref for Vec3<T> {
     type Target = [T];
     fn deref(&self) -> &Self::Target {
@@ -184,13 +1158,25 @@ impl<T> From<[T; 3]> for Vec3<T> {
     }
 }
 
-#[cfg(all(
-    any(target_arch = "x86", target_arch = "x86_64"),
-    target_feature = "avx2"
-))]
-mod simd {
-    use std::mem;
+/// Converts from the lane layout used when a `Vec3<i64>` is packed into an AVX2
+/// register with `_mm256_set_epi64x(x, y, z, 0)`; i.e. lanes in memory order are
+/// `[0, z, y, x]`. Lane 0 is padding and is ignored.
+impl From<[i64; 4]> for Vec3<i64> {
+    fn from(a: [i64; 4]) -> Self {
+        Vec3::new(a[3], a[2], a[1])
+    }
+}
 
+impl Vec3<i64> {
+    /// Inverse of `From<[i64; 4]>`: lays the vector out as `[0, z, y, x]`, the
+    /// memory order of the lanes of an AVX2 register holding it.
+    pub(crate) fn to_m256_array(self) -> [i64; 4] {
+        [0, self.z(), self.y(), self.x()]
+    }
+}
+
+#[cfg(all(test, any(target_arch = "x86", target_arch = "x86_64")))]
+mod simd_tests {
     use super::*;
 
     #[cfg(target_arch = "x86")]
@@ -198,17 +1184,20 @@ mod simd {
     #[cfg(target_arch = "x86_64")]
     use std::arch::x86_64::*;
 
-    impl From<__m256i> for Vec3<i64> {
-        fn from(v: __m256i) -> Self {
-            // safety: This is safe because the call to _mm256_storeu_si256 will write
-            // values to the uninitialized array so we won't be tying to access junk memory.
-            let mut a: [i64; 4] = unsafe { mem::MaybeUninit::uninit().assume_init() };
-            #[allow(clippy::cast_ptr_alignment)]
-            unsafe {
-                _mm256_storeu_si256(&mut a as *mut _ as *mut __m256i, v)
-            };
-            Vec3::new(a[3], a[2], a[1])
+    #[test]
+    fn test_m256_round_trip() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
         }
+        let v = Vec3::new(-7i64, 0, 42);
+        let mut array = [0i64; 4];
+        #[allow(clippy::cast_ptr_alignment)]
+        unsafe {
+            let m = _mm256_set_epi64x(v.x(), v.y(), v.z(), 0);
+            _mm256_storeu_si256(array.as_mut_ptr() as *mut __m256i, m);
+        }
+        assert_eq!(array, v.to_m256_array());
+        assert_eq!(Vec3::from(array), v);
     }
 }
 
@@ -217,15 +1206,156 @@ pub(crate) mod tests {
     use std::fs;
     use std::io;
 
+    use std::convert::TryFrom;
+
+    use super::{Vec2, Vec3, F64};
     use crate::error::Error;
 
+    #[test]
+    fn test_for_each_line() {
+        let input = "  first \n\n\tsecond\r\n   \nthird";
+        let mut lines = Vec::new();
+        super::for_each_line(input.as_bytes(), |lineno, line| {
+            lines.push((lineno, line.to_string()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                (1, "first".to_string()),
+                (3, "second".to_string()),
+                (5, "third".to_string()),
+            ]
+        );
+
+        let mut ncalls = 0;
+        let e = super::for_each_line(input.as_bytes(), |lineno, line| {
+            ncalls += 1;
+            if line == "second" {
+                bail!("stop at line {}", lineno);
+            }
+            Ok(())
+        })
+        .unwrap_err();
+        assert_eq!(e.to_string(), "stop at line 3");
+        assert_eq!(ncalls, 2);
+    }
+
+    #[test]
+    fn test_vec2_ops() {
+        assert_eq!(Vec2::new(1, 2) + Vec2::new(3, 4), Vec2::new(4, 6));
+        assert_eq!(Vec2::new(1, 2) - Vec2::new(3, 4), Vec2::new(-2, -2));
+        assert_eq!(Vec2::new(1, -2) * 3, Vec2::new(3, -6));
+        assert_eq!(Vec2::new(1, 2).manhattan(&Vec2::new(-3, 4)), 6);
+        assert_eq!(Vec2::new(5u64, 0).manhattan(&Vec2::new(2, 7)), 10);
+    }
+
+    #[test]
+    fn test_vec2_neighbors() {
+        let origin = Vec2::new(0i64, 0);
+        assert_eq!(
+            origin.neighbors4(),
+            [
+                Vec2::new(0, 1),
+                Vec2::new(0, -1),
+                Vec2::new(1, 0),
+                Vec2::new(-1, 0)
+            ]
+        );
+
+        let neighbors8 = origin.neighbors8();
+        let mut expected = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                if (x, y) != (0, 0) {
+                    expected.push(Vec2::new(x, y));
+                }
+            }
+        }
+        assert_eq!(neighbors8.len(), expected.len());
+        assert!(expected.iter().all(|p| neighbors8.contains(p)));
+        assert!(origin.neighbors4().iter().all(|p| neighbors8.contains(p)));
+        assert!(neighbors8.iter().all(|p| p.manhattan(&origin) <= 2));
+        assert_eq!(neighbors8[0], Vec2::new(0, 1));
+        assert_eq!(neighbors8[1], Vec2::new(1, 1));
+    }
+
+    #[test]
+    fn test_vec3_ops() {
+        let (a, b) = (Vec3::new(1, -2, 3), Vec3::new(4, 5, -6));
+        assert_eq!(a + b, Vec3::new(5, 3, -3));
+        assert_eq!(a - b, Vec3::new(-3, -7, 9));
+        assert_eq!(a * -2, Vec3::new(-2, 4, -6));
+        assert_eq!(a.dot(&b), 4 - 10 - 18);
+        assert_eq!(a.abs_sum(), 6);
+        assert_eq!(b.abs_sum(), 15);
+        assert_eq!(Vec3::new(0, 0, 0).abs_sum(), 0);
+    }
+
+    #[test]
+    fn test_f64_ord() {
+        let mut actual = [3.5, -0.0, -1.0, 0.0, 2.0, -7.25, 0.5]
+            .iter()
+            .map(|&f| F64::try_from(f).unwrap())
+            .collect::<Vec<_>>();
+        actual.sort();
+        let actual = actual.iter().map(|f| **f).collect::<Vec<_>>();
+        assert_eq!(actual, vec![-7.25, -1.0, 0.0, 0.0, 0.5, 2.0, 3.5]);
+        assert!(actual[2].is_sign_positive());
+
+        assert_eq!(F64::try_from(-0.0).unwrap(), F64::try_from(0.0).unwrap());
+        assert!(F64::try_from(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_f64_approx_eq() {
+        let a = F64::new(0.1 + 0.2).unwrap();
+        let b = F64::new(0.3).unwrap();
+        assert_ne!(a, b);
+        assert!(a.approx_eq(&b, 1e-9));
+        assert!(!a.approx_eq(&F64::new(0.31).unwrap(), 1e-9));
+        assert!(F64::new(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_vec3_m256_array() {
+        let v = Vec3::new(1i64, -2, 3);
+        assert_eq!(v.to_m256_array(), [0, 3, -2, 1]);
+        assert_eq!(Vec3::from(v.to_m256_array()), v);
+    }
+
+    /// Opens the puzzle input for `day`, or returns `None` (so the caller can skip
+    /// the test) if it hasn't been saved in `data/`. Puzzle inputs differ between
+    /// users, so a fresh checkout may not have them.
+    pub(crate) fn data_file(day: usize) -> Option<io::BufReader<fs::File>> {
+        open_if_exists(&format!("data/{:02}.txt", day))
+    }
+
+    fn open_if_exists(path: &str) -> Option<io::BufReader<fs::File>> {
+        match fs::File::open(path) {
+            Ok(file) => Some(io::BufReader::new(file)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("Skipping checks that need {}, which does not exist.", path);
+                None
+            }
+            Err(e) => panic!("Failed to open {}: {}", path, e),
+        }
+    }
+
+    #[test]
+    fn test_open_if_exists() {
+        assert!(open_if_exists("data/does-not-exist.txt").is_none());
+        assert!(open_if_exists("Cargo.toml").is_some());
+    }
+
     pub(crate) fn test_full_problem<F>(day: usize, run_func: F, expected1: &str, expected2: &str)
     where
         F: Fn(io::BufReader<fs::File>) -> Result<(String, String), Error>,
     {
-        let path = format!("data/{:02}.txt", day);
-        let file = std::fs::File::open(path).unwrap();
-        let reader = std::io::BufReader::new(file);
+        let Some(reader) = data_file(day) else {
+            return;
+        };
         let (actual1, actual2) = run_func(reader).unwrap();
         assert_eq!(&actual1, expected1);
         assert_eq!(&actual2, expected2);
@@ -0,0 +1,53 @@
+use std::io;
+
+use aoc2019::{day03, day03_v2};
+
+const NCASES: usize = 200;
+const MAX_SEGMENTS: u64 = 12;
+const MAX_DIST: u64 = 20;
+
+/// A small xorshift generator, so the test is reproducible without pulling in `rand`.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+fn random_path(rng: &mut Rng) -> String {
+    let nsegments = 1 + rng.below(MAX_SEGMENTS);
+    (0..nsegments)
+        .map(|_| {
+            let dir = ['U', 'D', 'L', 'R'][rng.below(4) as usize];
+            let dist = 1 + rng.below(MAX_DIST);
+            format!("{}{}", dir, dist)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[test]
+fn test_03_implementations_agree() {
+    let mut rng = Rng(0x2019_0003);
+    let mut nintersecting = 0;
+    for _ in 0..NCASES {
+        let input = format!("{}\n{}", random_path(&mut rng), random_path(&mut rng));
+        let expected =
+            day03::run_segments(io::BufReader::new(input.as_bytes())).map_err(|e| e.to_string());
+        let actual = day03_v2::run(io::BufReader::new(input.as_bytes())).map_err(|e| e.to_string());
+        assert_eq!(actual, expected, "input:\n{}", input);
+        if expected.is_ok() {
+            nintersecting += 1;
+        }
+    }
+    // Make sure the generated wires actually exercise the interesting case.
+    assert!(nintersecting > NCASES / 4);
+}
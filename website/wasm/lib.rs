@@ -21,6 +21,10 @@ impl Game {
         Ok(Self(game))
     }
 
+    pub fn reset(&mut self) {
+        self.0.reset()
+    }
+
     pub fn step(&mut self) -> Result<Option<i64>, JsValue> {
         let next_move = self.0.step().map_err(|e| e.to_string())?;
         Ok(next_move)
@@ -34,6 +38,10 @@ impl Game {
         self.0.display().len()
     }
 
+    pub fn display_string(&self) -> String {
+        self.0.display_string()
+    }
+
     pub fn rows(&self) -> usize {
         self.0.rows()
     }
@@ -46,6 +54,10 @@ impl Game {
         self.0.input(val)
     }
 
+    pub fn input_key(&mut self, key: &str) {
+        self.0.input_key(key)
+    }
+
     pub fn score(&self) -> i64 {
         self.0.score()
     }